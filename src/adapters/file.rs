@@ -0,0 +1,328 @@
+use crate::adapters::memory::{InMemoryClientRepository, InMemoryTransactionRepository};
+use crate::domain::model::{
+    AmountInMinorUnits, ClientId, FeeSchedule, FeeTier, FeeType, OverdraftPolicy,
+};
+use crate::domain::ports::{
+    EngineConfig, JournalEntry, JournalRepository, JournalRepositoryErrors, JOURNAL_VERSION,
+};
+use crate::domain::snapshot::{Snapshot, SnapshotErrors, SNAPSHOT_VERSION};
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use csv::{ReaderBuilder, Trim};
+use futures::prelude::stream::BoxStream;
+use futures::stream::{self, StreamExt};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Runs the engine with in-memory storage, but keeps the journal in an append-only file
+pub struct FileJournalEngineDeps;
+
+impl EngineConfig for FileJournalEngineDeps {
+    type ClientRepository = InMemoryClientRepository;
+    type TransactionRepository = InMemoryTransactionRepository;
+    type JournalRepository = FileJournalRepository;
+}
+
+/// Marks the start of a journal file, followed by the journal version
+const JOURNAL_MAGIC: [u8; 4] = *b"PEJL";
+
+/// Marks the start of a snapshot file, followed by the snapshot version
+const SNAPSHOT_MAGIC: [u8; 4] = *b"PESN";
+
+/// Journal entries are stored back to back as bincode records, after a header holding the version
+/// they were written with
+#[derive(Clone)]
+pub struct FileJournalRepository {
+    path: PathBuf,
+    writer: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl FileJournalRepository {
+    /// opens the journal at `path` for appending, creating it if it doesn't exist yet.
+    /// Existing journals are only appended to if they were written with the current version,
+    /// an entry left incomplete by a crash while it was written is dropped first.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, JournalRepositoryErrors> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to open journal {}", path.display()))?;
+        let length = file
+            .metadata()
+            .with_context(|| format!("failed to open journal {}", path.display()))?
+            .len();
+        if length == 0 {
+            file.write_all(&JOURNAL_MAGIC)
+                .and_then(|_| file.write_all(&JOURNAL_VERSION.to_le_bytes()))
+                .and_then(|_| file.flush())
+                .context("failed to write journal header")?;
+        } else {
+            let complete = complete_length(&file)?;
+            if complete < length {
+                file.set_len(complete)
+                    .context("failed to drop incomplete journal entry")?;
+            }
+        }
+        Ok(FileJournalRepository {
+            path,
+            writer: Arc::new(Mutex::new(BufWriter::new(file))),
+        })
+    }
+}
+
+#[async_trait]
+impl JournalRepository for FileJournalRepository {
+    async fn append(&mut self, entry: JournalEntry) -> Result<(), JournalRepositoryErrors> {
+        let mut writer = self.writer.lock().unwrap();
+        bincode::serialize_into(&mut *writer, &entry).context("failed to write journal entry")?;
+        // flush every entry so an applied transaction isn't lost when the process stops, the
+        // entry may still be lost along with the OS though as the file isn't synced to disk
+        writer.flush().context("failed to flush journal")?;
+        Ok(())
+    }
+
+    async fn get_all(
+        &self,
+    ) -> Result<
+        BoxStream<'static, Result<JournalEntry, JournalRepositoryErrors>>,
+        JournalRepositoryErrors,
+    > {
+        let file = File::open(&self.path)
+            .with_context(|| format!("failed to read journal {}", self.path.display()))?;
+        let mut reader = BufReader::new(file);
+        read_header(&mut reader)?;
+        let mut entries = Vec::new();
+        loop {
+            match next_entry(&mut reader) {
+                Ok(Some(entry)) => entries.push(entry),
+                Ok(None) => break,
+                Err(e) => {
+                    return Err(anyhow::Error::new(e)
+                        .context("corrupt journal entry")
+                        .into())
+                }
+            }
+        }
+        Ok(stream::iter(entries.into_iter().map(Ok)).boxed())
+    }
+}
+
+/// checks the journal starts with a header for the current version
+fn read_header(reader: &mut impl Read) -> Result<(), JournalRepositoryErrors> {
+    let mut magic = [0; 4];
+    let mut version = [0; 4];
+    match reader
+        .read_exact(&mut magic)
+        .and_then(|_| reader.read_exact(&mut version))
+    {
+        Ok(()) => (),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+            return Err(JournalRepositoryErrors::MissingVersion)
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context("failed to read journal")
+                .into())
+        }
+    }
+    if magic != JOURNAL_MAGIC {
+        return Err(JournalRepositoryErrors::MissingVersion);
+    }
+    match u32::from_le_bytes(version) {
+        JOURNAL_VERSION => Ok(()),
+        version => Err(JournalRepositoryErrors::UnsupportedVersion(version)),
+    }
+}
+
+/// reads the next journal entry, `None` if the journal ends right before it
+fn next_entry(reader: &mut impl BufRead) -> Result<Option<JournalEntry>, bincode::Error> {
+    // a journal may only end between entries, running out of bytes within one is an error
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    bincode::deserialize_from(reader).map(Some)
+}
+
+/// the length of the journal up to the end of its last complete entry
+fn complete_length(file: &File) -> Result<u64, JournalRepositoryErrors> {
+    let mut reader = BufReader::new(file);
+    read_header(&mut reader)?;
+    loop {
+        let end = reader.stream_position().context("failed to read journal")?;
+        match next_entry(&mut reader) {
+            Ok(Some(_)) => (),
+            Ok(None) => return Ok(end),
+            // only the last entry can be incomplete, as entries are only ever appended
+            Err(e) if is_end_of_file(&e) => return Ok(end),
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context("corrupt journal entry")
+                    .into())
+            }
+        }
+    }
+}
+
+fn is_end_of_file(error: &bincode::Error) -> bool {
+    matches!(error.as_ref(), bincode::ErrorKind::Io(e) if e.kind() == ErrorKind::UnexpectedEof)
+}
+
+/// reads a bincode encoded snapshot, refusing it before decoding unless its header holds the
+/// current version
+pub fn read_snapshot(path: impl AsRef<Path>) -> anyhow::Result<Snapshot> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).with_context(|| format!("failed to open snapshot {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    read_snapshot_header(&mut reader)
+        .with_context(|| format!("failed to read snapshot {}", path.display()))?;
+    bincode::deserialize_from(reader)
+        .with_context(|| format!("corrupt snapshot {}", path.display()))
+}
+
+/// writes a bincode encoded snapshot, replacing any existing file at `path`
+pub fn write_snapshot(path: impl AsRef<Path>, snapshot: &Snapshot) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    // write to a sibling file first so a crash never leaves a truncated snapshot behind
+    let tmp_path = path.with_extension("tmp");
+    let file = File::create(&tmp_path)
+        .with_context(|| format!("failed to create snapshot {}", tmp_path.display()))?;
+    let mut writer = BufWriter::new(file);
+    writer
+        .write_all(&SNAPSHOT_MAGIC)
+        .and_then(|_| writer.write_all(&snapshot.version.to_le_bytes()))
+        .context("failed to write snapshot header")?;
+    bincode::serialize_into(&mut writer, snapshot).context("failed to write snapshot")?;
+    writer.flush().context("failed to flush snapshot")?;
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("failed to replace snapshot {}", path.display()))
+}
+
+/// checks the snapshot starts with a header for the current version
+fn read_snapshot_header(reader: &mut impl Read) -> anyhow::Result<()> {
+    let mut magic = [0; 4];
+    let mut version = [0; 4];
+    match reader
+        .read_exact(&mut magic)
+        .and_then(|_| reader.read_exact(&mut version))
+    {
+        Ok(()) => (),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => bail!(SnapshotErrors::MissingVersion),
+        Err(e) => return Err(e.into()),
+    }
+    if magic != SNAPSHOT_MAGIC {
+        bail!(SnapshotErrors::MissingVersion);
+    }
+    match u32::from_le_bytes(version) {
+        SNAPSHOT_VERSION => Ok(()),
+        version => bail!(SnapshotErrors::UnsupportedVersion(version)),
+    }
+}
+
+/// reads per client overdraft limits from a CSV file with a `client` and `limit` column
+pub fn read_overdraft_limits(path: impl AsRef<Path>) -> anyhow::Result<OverdraftPolicy> {
+    #[derive(Deserialize)]
+    struct LimitRecord {
+        client: ClientId,
+        limit: AmountInMinorUnits,
+    }
+
+    let path = path.as_ref();
+    let mut reader = ReaderBuilder::new()
+        .trim(Trim::All)
+        .from_path(path)
+        .with_context(|| format!("failed to open overdraft limits {}", path.display()))?;
+    let mut limits = HashMap::new();
+    for record in reader.deserialize() {
+        let record: LimitRecord =
+            record.with_context(|| format!("invalid overdraft limit in {}", path.display()))?;
+        if record.limit < AmountInMinorUnits::default() {
+            bail!(
+                "negative overdraft limit {} for client {} in {}",
+                record.limit,
+                record.client.0,
+                path.display()
+            );
+        }
+        limits.insert(record.client, record.limit);
+    }
+    Ok(OverdraftPolicy::Table(limits))
+}
+
+/// Reads a fee schedule from a CSV file with one tier per row, crediting the fees to `house_account`.
+/// Every row names the transaction `type` it applies to, the `from`, `flat`, `percent`, `min` and
+/// `max` columns may be left empty.
+pub fn read_fee_schedule(
+    path: impl AsRef<Path>,
+    house_account: ClientId,
+) -> anyhow::Result<FeeSchedule> {
+    #[derive(Deserialize)]
+    struct TierRecord {
+        #[serde(rename = "type")]
+        tx_type: FeeType,
+        #[serde(default)]
+        from: Option<AmountInMinorUnits>,
+        #[serde(default)]
+        flat: Option<AmountInMinorUnits>,
+        #[serde(default)]
+        percent: Option<Decimal>,
+        #[serde(default)]
+        min: Option<AmountInMinorUnits>,
+        #[serde(default)]
+        max: Option<AmountInMinorUnits>,
+    }
+
+    let path = path.as_ref();
+    let mut reader = ReaderBuilder::new()
+        .trim(Trim::All)
+        .from_path(path)
+        .with_context(|| format!("failed to open fee schedule {}", path.display()))?;
+    let mut tiers = Vec::new();
+    for (row, record) in reader.deserialize().enumerate() {
+        let record: TierRecord =
+            record.with_context(|| format!("invalid fee tier in {}", path.display()))?;
+        let tier = FeeTier {
+            tx_type: record.tx_type,
+            from: record.from.unwrap_or_default(),
+            flat: record.flat.unwrap_or_default(),
+            percent: record.percent.unwrap_or_default(),
+            min: record.min,
+            max: record.max,
+        };
+        let zero = AmountInMinorUnits::default();
+        let negative_amount = [
+            Some(&tier.from),
+            Some(&tier.flat),
+            tier.min.as_ref(),
+            tier.max.as_ref(),
+        ]
+        .iter()
+        .flatten()
+        .any(|amount| **amount < zero);
+        if negative_amount || tier.percent.is_sign_negative() || tier.percent > Decimal::from(100) {
+            bail!(
+                "fee tier {} in {} must have non-negative amounts and a percentage of at most 100",
+                row + 1,
+                path.display()
+            );
+        }
+        if let (Some(min), Some(max)) = (&tier.min, &tier.max) {
+            if min > max {
+                bail!(
+                    "fee tier {} in {} has a minimum above its maximum",
+                    row + 1,
+                    path.display()
+                );
+            }
+        }
+        tiers.push(tier);
+    }
+    Ok(FeeSchedule::new(house_account, tiers))
+}
@@ -1,14 +1,14 @@
-use crate::domain::model::{
-    AmountInMinorUnits, Client, ClientId, TransactionId, TransactionStatus,
-};
+use crate::domain::model::{Client, ClientId, StoredTransaction, TransactionId};
 use crate::domain::ports::{
-    ClientRepository, ClientRepositoryErrors, ClientUpdate, EngineConfig,
-    TransactionRepositoryErrors, TransactionsRepository,
+    ClientRepository, ClientRepositoryErrors, ClientUpdate, EngineConfig, JournalEntry,
+    JournalRepository, JournalRepositoryErrors, StatusUpdate, TransactionRepositoryErrors,
+    TransactionsRepository, UnitOfWork, UnitOfWorkErrors,
 };
 use async_trait::async_trait;
 use futures::prelude::stream::BoxStream;
 use futures::stream::{self, StreamExt};
 use std::collections::HashMap;
+use std::hash::Hash;
 use std::sync::{Arc, RwLock};
 
 /// Constraining the deps to the appropriate concrete impls to run the engine with in-memory storage
@@ -18,10 +18,11 @@ pub struct InMemoryEngineDeps;
 impl EngineConfig for InMemoryEngineDeps {
     type ClientRepository = InMemoryClientRepository;
     type TransactionRepository = InMemoryTransactionRepository;
+    type JournalRepository = InMemoryJournalRepository;
 }
 
 #[derive(Clone, Default)]
-pub struct InMemoryClientRepository(Arc<RwLock<HashMap<ClientId, Client>>>);
+pub struct InMemoryClientRepository(Arc<RwLock<StagedMap<ClientId, Client>>>);
 
 #[async_trait]
 impl ClientRepository for InMemoryClientRepository {
@@ -30,7 +31,7 @@ impl ClientRepository for InMemoryClientRepository {
     ) -> Result<BoxStream<'static, Result<Client, ClientRepositoryErrors>>, ClientRepositoryErrors>
     {
         let inner = self.0.read().unwrap();
-        let clients: Vec<Client> = inner.values().cloned().collect();
+        let clients: Vec<Client> = inner.values();
         let stream = stream::iter(clients.into_iter().map(Ok));
         Ok(stream.boxed())
     }
@@ -46,7 +47,7 @@ impl ClientRepository for InMemoryClientRepository {
 
     async fn insert(&mut self, client: Client) -> Result<(), ClientRepositoryErrors> {
         let mut inner = self.0.write().unwrap();
-        let _ = inner.insert(client.id, client);
+        inner.insert(client.id, client);
         Ok(())
     }
 
@@ -57,7 +58,7 @@ impl ClientRepository for InMemoryClientRepository {
     ) -> Result<(), ClientRepositoryErrors> {
         let mut inner = self.0.write().unwrap();
         // insert default client if none exist yet
-        if !inner.contains_key(id) {
+        if inner.get(id).is_none() {
             inner.insert(
                 *id,
                 Client {
@@ -66,139 +67,403 @@ impl ClientRepository for InMemoryClientRepository {
                 },
             );
         }
-        let mut client = inner.get_mut(id).unwrap();
+        let client = inner.get_mut(id).unwrap();
+        // apply to a copy so a failed update leaves the client untouched
+        let mut updated = client.clone();
+        let overflow = || ClientRepositoryErrors::BalanceOverflow(*id);
         match update {
             ClientUpdate::Deposit {
                 available_increase,
                 total_increase,
             } => {
-                client.available = client.available.clone() + available_increase;
-                client.total = client.total.clone() + total_increase;
+                updated.available = updated
+                    .available
+                    .checked_add(available_increase)
+                    .ok_or_else(overflow)?;
+                updated.total = updated
+                    .total
+                    .checked_add(total_increase)
+                    .ok_or_else(overflow)?;
             }
             ClientUpdate::Withdrawal {
                 available_decrease,
                 total_decrease,
             } => {
-                client.available = client.available.clone() - available_decrease;
-                client.total = client.total.clone() - total_decrease;
+                updated.available = updated
+                    .available
+                    .checked_sub(available_decrease)
+                    .ok_or_else(overflow)?;
+                updated.total = updated
+                    .total
+                    .checked_sub(total_decrease)
+                    .ok_or_else(overflow)?;
             }
             ClientUpdate::Dispute {
                 available_decrease,
                 held_increase,
             } => {
-                client.available = client.available.clone() - available_decrease;
-                client.held = client.held.clone() + held_increase;
+                updated.available = updated
+                    .available
+                    .checked_sub(available_decrease)
+                    .ok_or_else(overflow)?;
+                updated.held = updated
+                    .held
+                    .checked_add(held_increase)
+                    .ok_or_else(overflow)?;
             }
             ClientUpdate::Resolve {
                 available_increase,
                 held_decrease,
             } => {
-                client.available = client.available.clone() + available_increase;
-                client.held = client.held.clone() - held_decrease;
+                updated.available = updated
+                    .available
+                    .checked_add(available_increase)
+                    .ok_or_else(overflow)?;
+                updated.held = updated
+                    .held
+                    .checked_sub(held_decrease)
+                    .ok_or_else(overflow)?;
             }
             ClientUpdate::Chargeback {
                 held_decrease,
                 total_decrease,
             } => {
-                client.held = client.held.clone() - held_decrease;
-                client.total = client.total.clone() - total_decrease;
-                client.locked = true;
+                updated.held = updated
+                    .held
+                    .checked_sub(held_decrease)
+                    .ok_or_else(overflow)?;
+                updated.total = updated
+                    .total
+                    .checked_sub(total_decrease)
+                    .ok_or_else(overflow)?;
+                updated.locked = true;
+            }
+            ClientUpdate::WithdrawalDispute {
+                held_increase,
+                total_increase,
+            } => {
+                updated.held = updated
+                    .held
+                    .checked_add(held_increase)
+                    .ok_or_else(overflow)?;
+                updated.total = updated
+                    .total
+                    .checked_add(total_increase)
+                    .ok_or_else(overflow)?;
+            }
+            ClientUpdate::WithdrawalResolve {
+                held_decrease,
+                total_decrease,
+            } => {
+                updated.held = updated
+                    .held
+                    .checked_sub(held_decrease)
+                    .ok_or_else(overflow)?;
+                updated.total = updated
+                    .total
+                    .checked_sub(total_decrease)
+                    .ok_or_else(overflow)?;
+            }
+            ClientUpdate::WithdrawalChargeback {
+                held_decrease,
+                available_increase,
+            } => {
+                updated.held = updated
+                    .held
+                    .checked_sub(held_decrease)
+                    .ok_or_else(overflow)?;
+                updated.available = updated
+                    .available
+                    .checked_add(available_increase)
+                    .ok_or_else(overflow)?;
+            }
+            ClientUpdate::Authorize {
+                available_decrease,
+                held_increase,
+            } => {
+                updated.available = updated
+                    .available
+                    .checked_sub(available_decrease)
+                    .ok_or_else(overflow)?;
+                updated.held = updated
+                    .held
+                    .checked_add(held_increase)
+                    .ok_or_else(overflow)?;
+            }
+            ClientUpdate::Capture {
+                held_decrease,
+                total_decrease,
+            } => {
+                updated.held = updated
+                    .held
+                    .checked_sub(held_decrease)
+                    .ok_or_else(overflow)?;
+                updated.total = updated
+                    .total
+                    .checked_sub(total_decrease)
+                    .ok_or_else(overflow)?;
+            }
+            ClientUpdate::Void {
+                held_decrease,
+                available_increase,
+            } => {
+                updated.held = updated
+                    .held
+                    .checked_sub(held_decrease)
+                    .ok_or_else(overflow)?;
+                updated.available = updated
+                    .available
+                    .checked_add(available_increase)
+                    .ok_or_else(overflow)?;
+            }
+            ClientUpdate::Fee {
+                available_decrease,
+                total_decrease,
+            } => {
+                updated.available = updated
+                    .available
+                    .checked_sub(available_decrease)
+                    .ok_or_else(overflow)?;
+                updated.total = updated
+                    .total
+                    .checked_sub(total_decrease)
+                    .ok_or_else(overflow)?;
+            }
+            ClientUpdate::FeeIncome {
+                available_increase,
+                total_increase,
+            } => {
+                updated.available = updated
+                    .available
+                    .checked_add(available_increase)
+                    .ok_or_else(overflow)?;
+                updated.total = updated
+                    .total
+                    .checked_add(total_increase)
+                    .ok_or_else(overflow)?;
+            }
+            ClientUpdate::Unlock => updated.locked = false,
+            ClientUpdate::Freeze => updated.locked = true,
+            ClientUpdate::Close {
+                available_decrease,
+                total_decrease,
+            } => {
+                updated.available = updated
+                    .available
+                    .checked_sub(available_decrease)
+                    .ok_or_else(overflow)?;
+                updated.total = updated
+                    .total
+                    .checked_sub(total_decrease)
+                    .ok_or_else(overflow)?;
+                updated.closed = true;
             }
         }
+        *client = updated;
         Ok(())
     }
 }
 
-#[derive(Clone, Default)]
-pub struct InMemoryTransactionRepository(Arc<RwLock<InnerTransactionRepository>>);
+#[async_trait]
+impl UnitOfWork for InMemoryClientRepository {
+    async fn begin(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.write().unwrap().begin()
+    }
 
-// use inner wrapper so Arc<RwLock<>> can cover multiple hashmaps
-#[derive(Default)]
-struct InnerTransactionRepository {
-    transaction_status: HashMap<TransactionId, TransactionStatus>,
-    transaction_value: HashMap<TransactionId, AmountInMinorUnits>,
+    async fn prepare(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.read().unwrap().prepare()
+    }
+
+    async fn commit(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.write().unwrap().commit()
+    }
+
+    async fn rollback(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.write().unwrap().rollback()
+    }
 }
 
+#[derive(Clone, Default)]
+pub struct InMemoryTransactionRepository(Arc<RwLock<StagedMap<TransactionId, StoredTransaction>>>);
+
 #[async_trait]
 impl TransactionsRepository for InMemoryTransactionRepository {
-    async fn get_transaction_status(
+    async fn get(
         &self,
         transaction_id: &TransactionId,
-    ) -> Result<TransactionStatus, TransactionRepositoryErrors> {
+    ) -> Result<StoredTransaction, TransactionRepositoryErrors> {
         self.0
             .read()
             .unwrap()
-            .get_transaction_status(transaction_id)
+            .get(transaction_id)
+            .cloned()
+            .ok_or_else(|| TransactionRepositoryErrors::TransactionNotFound(*transaction_id))
     }
 
-    async fn store_transaction_status(
+    async fn insert(
         &mut self,
-        transaction_id: TransactionId,
-        transaction_status: TransactionStatus,
+        transaction: StoredTransaction,
     ) -> Result<(), TransactionRepositoryErrors> {
-        self.0
-            .write()
-            .unwrap()
-            .store_transaction_status(transaction_id, transaction_status);
+        let mut inner = self.0.write().unwrap();
+        inner.insert(transaction.id, transaction);
         Ok(())
     }
 
-    async fn get_transaction_value(
-        &self,
-        transaction_id: &TransactionId,
-    ) -> Result<AmountInMinorUnits, TransactionRepositoryErrors> {
-        self.0.read().unwrap().get_transaction_value(transaction_id)
-    }
-
-    async fn store_transaction_value(
+    async fn update_status(
         &mut self,
-        transaction_id: TransactionId,
-        amount: AmountInMinorUnits,
+        transaction_id: &TransactionId,
+        update: StatusUpdate,
     ) -> Result<(), TransactionRepositoryErrors> {
-        self.0
-            .write()
-            .unwrap()
-            .store_transaction_value(transaction_id, amount);
+        let mut inner = self.0.write().unwrap();
+        let transaction = inner
+            .get_mut(transaction_id)
+            .ok_or_else(|| TransactionRepositoryErrors::TransactionNotFound(*transaction_id))?;
+        transaction.status = update.status;
+        transaction.disputed = update.disputed;
+        transaction.charged_back = update.charged_back;
+        transaction.resolved = update.resolved;
+        transaction.disputes = update.disputes;
+        transaction.disputed_at = update.disputed_at;
         Ok(())
     }
-}
 
-impl InnerTransactionRepository {
-    fn get_transaction_status(
+    async fn get_all(
         &self,
-        transaction_id: &TransactionId,
-    ) -> Result<TransactionStatus, TransactionRepositoryErrors> {
-        self.transaction_status
-            .get(transaction_id)
-            .cloned()
-            .ok_or_else(|| TransactionRepositoryErrors::TransactionNotFound(*transaction_id))
+    ) -> Result<
+        BoxStream<'static, Result<StoredTransaction, TransactionRepositoryErrors>>,
+        TransactionRepositoryErrors,
+    > {
+        let inner = self.0.read().unwrap();
+        let transactions: Vec<StoredTransaction> = inner.values();
+        let stream = stream::iter(transactions.into_iter().map(Ok));
+        Ok(stream.boxed())
     }
+}
 
-    fn store_transaction_status(
-        &mut self,
-        transaction_id: TransactionId,
-        transaction_status: TransactionStatus,
-    ) {
-        let _ = self
-            .transaction_status
-            .insert(transaction_id, transaction_status);
+#[async_trait]
+impl UnitOfWork for InMemoryTransactionRepository {
+    async fn begin(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.write().unwrap().begin()
     }
 
-    fn store_transaction_value(
-        &mut self,
-        transaction_id: TransactionId,
-        amount: AmountInMinorUnits,
-    ) {
-        let _ = self.transaction_value.insert(transaction_id, amount);
+    async fn prepare(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.read().unwrap().prepare()
+    }
+
+    async fn commit(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.write().unwrap().commit()
     }
 
-    fn get_transaction_value(
+    async fn rollback(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.write().unwrap().rollback()
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryJournalRepository(Arc<RwLock<Vec<JournalEntry>>>);
+
+#[async_trait]
+impl JournalRepository for InMemoryJournalRepository {
+    async fn append(&mut self, entry: JournalEntry) -> Result<(), JournalRepositoryErrors> {
+        self.0.write().unwrap().push(entry);
+        Ok(())
+    }
+
+    async fn get_all(
         &self,
-        transaction_id: &TransactionId,
-    ) -> Result<AmountInMinorUnits, TransactionRepositoryErrors> {
-        self.transaction_value
-            .get(transaction_id)
-            .cloned()
-            .ok_or_else(|| TransactionRepositoryErrors::TransactionNotFound(*transaction_id))
+    ) -> Result<
+        BoxStream<'static, Result<JournalEntry, JournalRepositoryErrors>>,
+        JournalRepositoryErrors,
+    > {
+        let entries = self.0.read().unwrap().clone();
+        let stream = stream::iter(entries.into_iter().map(Ok));
+        Ok(stream.boxed())
+    }
+}
+
+/// A hashmap which keeps rows written during a unit of work separate from the committed rows,
+/// so they can be discarded as a whole on rollback. Writes outside of a unit of work are
+/// applied to the committed rows directly.
+struct StagedMap<K, V> {
+    committed: HashMap<K, V>,
+    staged: Option<HashMap<K, V>>,
+}
+
+impl<K, V> Default for StagedMap<K, V> {
+    fn default() -> Self {
+        StagedMap {
+            committed: HashMap::new(),
+            staged: None,
+        }
+    }
+}
+
+impl<K, V> StagedMap<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    fn get(&self, key: &K) -> Option<&V> {
+        self.staged
+            .as_ref()
+            .and_then(|staged| staged.get(key))
+            .or_else(|| self.committed.get(key))
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        match &mut self.staged {
+            Some(staged) => {
+                // copy the committed row into the stage before handing out a mutable reference
+                if !staged.contains_key(key) {
+                    let row = self.committed.get(key)?.clone();
+                    staged.insert(key.clone(), row);
+                }
+                staged.get_mut(key)
+            }
+            None => self.committed.get_mut(key),
+        }
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        let _ = match &mut self.staged {
+            Some(staged) => staged.insert(key, value),
+            None => self.committed.insert(key, value),
+        };
+    }
+
+    fn values(&self) -> Vec<V> {
+        let mut rows = self.committed.clone();
+        if let Some(staged) = &self.staged {
+            rows.extend(staged.clone());
+        }
+        rows.into_values().collect()
+    }
+
+    fn begin(&mut self) -> Result<(), UnitOfWorkErrors> {
+        if self.staged.is_some() {
+            return Err(UnitOfWorkErrors::AlreadyStarted);
+        }
+        self.staged = Some(HashMap::new());
+        Ok(())
+    }
+
+    // staged rows are only moved into memory, so the only thing which could fail a commit is a
+    // missing unit of work
+    fn prepare(&self) -> Result<(), UnitOfWorkErrors> {
+        match self.staged {
+            Some(_) => Ok(()),
+            None => Err(UnitOfWorkErrors::NotStarted),
+        }
+    }
+
+    fn commit(&mut self) -> Result<(), UnitOfWorkErrors> {
+        let staged = self.staged.take().ok_or(UnitOfWorkErrors::NotStarted)?;
+        self.committed.extend(staged);
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.staged.take().ok_or(UnitOfWorkErrors::NotStarted)?;
+        Ok(())
     }
 }
@@ -1,3 +1,5 @@
 pub mod engine;
 pub mod model;
 pub mod ports;
+pub mod replay;
+pub mod snapshot;
@@ -1,18 +1,43 @@
 use crate::domain::model::{
-    Chargeback, Client, Deposit, Dispute, Resolve, Transaction, TransactionStatus, Withdrawal,
+    AmountInMinorUnits, AuthorizationExpiry, Authorize, Capture, Chargeback, Client, ClientId,
+    Close, Deposit, Dispute, DisputeEvent, DisputeLifecycle, DisputeStep, DisputeWindow,
+    DuplicatePolicy, FeeSchedule, FeeType, Freeze, LockAction, LockPolicy, OverdraftPolicy,
+    RecordTime, RejectionReason, Resolve, StoredTransaction, TimedTransaction, Transaction,
+    TransactionId, TransactionKind, TransactionOutcome, TransactionStatus, Transfer, Unlock, Void,
+    Withdrawal,
 };
 use crate::domain::ports::{
-    ClientRepository, ClientUpdate, Engine, EngineConfig, EngineErrors, EngineResult,
-    TransactionRepositoryErrors, TransactionsRepository,
+    ClientRepository, ClientRepositoryErrors, ClientUpdate, Engine, EngineConfig, EngineErrors,
+    EngineResult, JournalEntry, JournalRepository, JournaledChange, StatusUpdate,
+    TransactionRepositoryErrors, TransactionsRepository, UnitOfWork,
 };
 use async_trait::async_trait;
 use futures::prelude::stream::BoxStream;
 use futures::{StreamExt, TryStreamExt};
+use std::collections::BTreeSet;
 
 #[derive(Default, Debug)]
 pub struct TransactionEngine<T: EngineConfig> {
     clients: T::ClientRepository,
     transactions: T::TransactionRepository,
+    journal: T::JournalRepository,
+    lock_policy: LockPolicy,
+    overdraft_policy: OverdraftPolicy,
+    duplicate_policy: DuplicatePolicy,
+    dispute_lifecycle: DisputeLifecycle,
+    dispute_window: DisputeWindow,
+    authorization_expiry: AuthorizationExpiry,
+    fee_schedule: FeeSchedule,
+    // number of transaction records processed so far, used to order stored transactions
+    sequence: u64,
+    // when the record currently being processed was created
+    now: RecordTime,
+    // transactions which may still be disputed, loaded from the repository on first use
+    open_disputes: Option<BTreeSet<TransactionId>>,
+    // authorizations which may still expire, loaded from the repository on first use
+    open_authorizations: Option<BTreeSet<TransactionId>>,
+    // repository changes made by the transaction currently being applied
+    changes: Vec<JournaledChange>,
 }
 
 #[async_trait]
@@ -20,14 +45,16 @@ impl<T> Engine for TransactionEngine<T>
 where
     T: EngineConfig,
 {
-    async fn process_transaction(&mut self, transaction: Transaction) -> EngineResult {
-        match transaction {
-            Transaction::Deposit(deposit) => self.process_deposit(deposit).await,
-            Transaction::Withdrawal(withdrawal) => self.process_withdrawal(withdrawal).await,
-            Transaction::Dispute(dispute) => self.process_dispute(dispute).await,
-            Transaction::Resolve(resolve) => self.process_resolve(resolve).await,
-            Transaction::Chargeback(chargeback) => self.process_chargeback(chargeback).await,
-        }
+    async fn process_timed_transaction(&mut self, record: TimedTransaction) -> EngineResult {
+        self.sequence = record.sequence.unwrap_or(self.sequence + 1);
+        self.now = RecordTime {
+            sequence: self.sequence,
+            timestamp: record.timestamp,
+        };
+
+        self.resolve_overdue_disputes().await?;
+        self.void_expired_authorizations().await?;
+        self.apply_in_unit_of_work(record.transaction).await
     }
 
     async fn get_clients(
@@ -46,124 +73,1077 @@ impl<T> TransactionEngine<T>
 where
     T: EngineConfig,
 {
-    async fn process_deposit(&mut self, deposit: Deposit) -> EngineResult {
-        if let Err(TransactionRepositoryErrors::TransactionNotFound(_)) =
-            self.transactions.get_transaction_status(&deposit.tx).await
-        {
-            self.transactions
-                .store_transaction_value(deposit.tx, deposit.amount.clone())
-                .await?;
-            self.transactions
-                .store_transaction_status(deposit.tx, TransactionStatus::Processed)
-                .await?;
-            self.clients
-                .update(
-                    &deposit.client,
-                    ClientUpdate::Deposit {
-                        available_increase: deposit.amount.clone(),
-                        total_increase: deposit.amount,
-                    },
-                )
-                .await?;
+    pub fn new(
+        clients: T::ClientRepository,
+        transactions: T::TransactionRepository,
+        journal: T::JournalRepository,
+    ) -> Self {
+        TransactionEngine {
+            clients,
+            transactions,
+            journal,
+            lock_policy: LockPolicy::default(),
+            overdraft_policy: OverdraftPolicy::default(),
+            duplicate_policy: DuplicatePolicy::default(),
+            dispute_lifecycle: DisputeLifecycle::default(),
+            dispute_window: DisputeWindow::default(),
+            authorization_expiry: AuthorizationExpiry::default(),
+            fee_schedule: FeeSchedule::default(),
+            sequence: 0,
+            now: RecordTime::default(),
+            open_disputes: None,
+            open_authorizations: None,
+            changes: Vec::new(),
         }
+    }
+
+    pub fn with_lock_policy(mut self, lock_policy: LockPolicy) -> Self {
+        self.lock_policy = lock_policy;
+        self
+    }
+
+    pub fn with_overdraft_policy(mut self, overdraft_policy: OverdraftPolicy) -> Self {
+        self.overdraft_policy = overdraft_policy;
+        self
+    }
+
+    pub fn with_duplicate_policy(mut self, duplicate_policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = duplicate_policy;
+        self
+    }
+
+    pub fn with_dispute_lifecycle(mut self, dispute_lifecycle: DisputeLifecycle) -> Self {
+        self.dispute_lifecycle = dispute_lifecycle;
+        self
+    }
+
+    pub fn with_dispute_window(mut self, dispute_window: DisputeWindow) -> Self {
+        self.dispute_window = dispute_window;
+        self
+    }
 
+    pub fn with_authorization_expiry(mut self, authorization_expiry: AuthorizationExpiry) -> Self {
+        self.authorization_expiry = authorization_expiry;
+        self
+    }
+
+    pub fn with_fee_schedule(mut self, fee_schedule: FeeSchedule) -> Self {
+        self.fee_schedule = fee_schedule;
+        self
+    }
+
+    /// continues numbering transaction records after `sequence`, used when resuming from prior state
+    pub fn with_sequence(mut self, sequence: u64) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    /// sequence number of the last transaction processed
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Moves the engine's clock forward to `now` without processing a record, resolving the
+    /// disputes which are overdue and voiding the authorizations which expired by then. Used when
+    /// records processed by other engines move time forward for this one too.
+    pub async fn advance_to(&mut self, now: RecordTime) -> Result<(), EngineErrors> {
+        if now.sequence <= self.sequence {
+            return Ok(());
+        }
+        self.sequence = now.sequence;
+        self.now = now;
+        self.resolve_overdue_disputes().await?;
+        self.void_expired_authorizations().await
+    }
+
+    /// Takes over the clients and transactions of `other`, used to carry on with a single engine
+    /// where several engines processed parts of the input so far. The engines have to hold
+    /// disjoint clients & transactions, the journal of `other` is dropped.
+    pub async fn absorb(&mut self, other: TransactionEngine<T>) -> Result<(), EngineErrors> {
+        let mut clients = other.clients.get_all().await?;
+        while let Some(client) = clients.try_next().await? {
+            self.clients.insert(client).await?;
+        }
+        let mut transactions = other.transactions.get_all().await?;
+        while let Some(transaction) = transactions.try_next().await? {
+            self.transactions.insert(transaction).await?;
+        }
+        self.sequence = self.sequence.max(other.sequence);
+        // the absorbed transactions may be disputed or authorized, so these are loaded again
+        self.open_disputes = None;
+        self.open_authorizations = None;
         Ok(())
     }
 
-    async fn process_withdrawal(&mut self, withdrawal: Withdrawal) -> EngineResult {
-        let client = self.clients.get(&withdrawal.client).await?;
-        if client.available > withdrawal.amount {
-            self.clients
-                .update(
-                    &withdrawal.client,
-                    ClientUpdate::Withdrawal {
-                        available_decrease: withdrawal.amount.clone(),
-                        total_decrease: withdrawal.amount.clone(),
-                    },
-                )
-                .await?;
+    async fn apply_in_unit_of_work(&mut self, transaction: Transaction) -> EngineResult {
+        self.changes.clear();
+
+        // every transaction is applied as a single unit of work across both repositories
+        self.begin_unit_of_work().await?;
+        let outcome = match self.apply_transaction(transaction.clone()).await {
+            Ok(outcome) => outcome,
+            // a balance that can't be represented only affects this transaction
+            Err(EngineErrors::ClientError(ClientRepositoryErrors::BalanceOverflow(_))) => {
+                self.rollback_unit_of_work().await?;
+                return Ok(TransactionOutcome::Rejected {
+                    reason: RejectionReason::BalanceOverflow,
+                });
+            }
+            Err(e) => {
+                self.rollback_unit_of_work().await?;
+                return Err(e);
+            }
+        };
+        self.commit_unit_of_work().await?;
+        // only journal changes which were actually persisted
+        if outcome == TransactionOutcome::Applied {
+            self.journal_applied(transaction).await?;
+        }
+        Ok(outcome)
+    }
+
+    async fn begin_unit_of_work(&mut self) -> Result<(), EngineErrors> {
+        self.clients.begin().await?;
+        if let Err(e) = self.transactions.begin().await {
+            self.clients.rollback().await?;
+            return Err(e.into());
         }
         Ok(())
     }
 
-    async fn process_dispute(&mut self, dispute: Dispute) -> EngineResult {
-        let status = self
-            .transactions
-            .get_transaction_status(&dispute.tx)
-            .await?;
+    /// prepares both repositories before committing either, so one of them refusing its writes
+    /// can't leave the other one's persisted
+    async fn commit_unit_of_work(&mut self) -> Result<(), EngineErrors> {
+        let prepared = match self.clients.prepare().await {
+            Ok(()) => self.transactions.prepare().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = prepared {
+            self.rollback_unit_of_work().await?;
+            return Err(e.into());
+        }
+        self.clients.commit().await?;
+        self.transactions.commit().await?;
+        Ok(())
+    }
+
+    async fn rollback_unit_of_work(&mut self) -> Result<(), EngineErrors> {
+        // roll back both repositories even if the first one fails
+        let clients = self.clients.rollback().await;
+        self.transactions.rollback().await?;
+        clients?;
+        Ok(())
+    }
 
-        // Only handle dispute if transaction is in the base processed state
-        if status == TransactionStatus::Processed {
-            let amount = self.transactions.get_transaction_value(&dispute.tx).await?;
-            self.transactions
-                .store_transaction_status(dispute.tx, TransactionStatus::Disputed)
-                .await?;
-            self.clients
-                .update(
-                    &dispute.client,
-                    ClientUpdate::Dispute {
-                        available_decrease: amount.clone(),
-                        held_increase: amount,
-                    },
-                )
-                .await?;
+    /// resolves every dispute left open past the resolution deadline, as if a resolve record for it
+    /// had been received just before the current record
+    async fn resolve_overdue_disputes(&mut self) -> Result<(), EngineErrors> {
+        if self.dispute_window.resolution_deadline.is_none() {
+            return Ok(());
         }
+        let open_disputes = match self.open_disputes.take() {
+            Some(open_disputes) => open_disputes,
+            None => self.load_with_status(TransactionStatus::Disputed).await?,
+        };
+
+        let mut still_open = BTreeSet::new();
+        for id in open_disputes {
+            // disputes settled since they were opened drop out of the set
+            let stored = match self.find_transaction(&id).await? {
+                Some(stored) if stored.status == TransactionStatus::Disputed => stored,
+                _ => continue,
+            };
+            if !self.dispute_window.is_overdue(&stored, &self.now) {
+                still_open.insert(id);
+                continue;
+            }
+            let resolve = Transaction::Resolve(Resolve {
+                client: stored.client,
+                tx: id,
+                amount: None,
+            });
+            // e.g. a lock policy refusing resolves, the dispute is retried with the next record
+            if self.apply_in_unit_of_work(resolve).await? != TransactionOutcome::Applied {
+                still_open.insert(id);
+            }
+        }
+        self.open_disputes = Some(still_open);
         Ok(())
     }
 
-    async fn process_resolve(&mut self, resolve: Resolve) -> EngineResult {
-        let state = self
-            .transactions
-            .get_transaction_status(&resolve.tx)
-            .await?;
+    /// voids every authorization which expired, as if a void record for it had been received just
+    /// before the current record
+    async fn void_expired_authorizations(&mut self) -> Result<(), EngineErrors> {
+        if self.authorization_expiry.max_age.is_none() {
+            return Ok(());
+        }
+        let open_authorizations = match self.open_authorizations.take() {
+            Some(open_authorizations) => open_authorizations,
+            None => self.load_with_status(TransactionStatus::Authorized).await?,
+        };
 
-        // only process resolution if transaction is in a disputed state
-        if state == TransactionStatus::Disputed {
-            let amount = self.transactions.get_transaction_value(&resolve.tx).await?;
-            self.transactions
-                .store_transaction_status(resolve.tx, TransactionStatus::Resolved)
-                .await?;
-            self.clients
-                .update(
-                    &resolve.client,
-                    ClientUpdate::Resolve {
-                        available_increase: amount.clone(),
-                        held_decrease: amount.clone(),
-                    },
-                )
-                .await?;
+        let mut still_open = BTreeSet::new();
+        for id in open_authorizations {
+            // authorizations captured or voided since they were made drop out of the set
+            let stored = match self.find_transaction(&id).await? {
+                Some(stored) if stored.status == TransactionStatus::Authorized => stored,
+                _ => continue,
+            };
+            if !self.authorization_expiry.is_expired(&stored, &self.now) {
+                still_open.insert(id);
+                continue;
+            }
+            let void = Transaction::Void(Void {
+                client: stored.client,
+                tx: id,
+            });
+            if self.apply_in_unit_of_work(void).await? != TransactionOutcome::Applied {
+                still_open.insert(id);
+            }
         }
+        self.open_authorizations = Some(still_open);
         Ok(())
     }
 
+    /// ids of all stored transactions currently in `status`
+    async fn load_with_status(
+        &self,
+        status: TransactionStatus,
+    ) -> Result<BTreeSet<TransactionId>, EngineErrors> {
+        let mut ids = BTreeSet::new();
+        let mut transactions = self.transactions.get_all().await?;
+        while let Some(stored) = transactions.try_next().await? {
+            if stored.status == status {
+                ids.insert(stored.id);
+            }
+        }
+        Ok(ids)
+    }
+
+    async fn apply_transaction(&mut self, transaction: Transaction) -> EngineResult {
+        if let Some(reason) = self.account_restriction(&transaction).await? {
+            return Ok(TransactionOutcome::Rejected { reason });
+        }
+
+        match transaction {
+            Transaction::Deposit(deposit) => self.process_deposit(deposit).await,
+            Transaction::Withdrawal(withdrawal) => self.process_withdrawal(withdrawal).await,
+            Transaction::Transfer(transfer) => self.process_transfer(transfer).await,
+            Transaction::Authorize(authorize) => self.process_authorize(authorize).await,
+            Transaction::Capture(capture) => self.process_capture(capture).await,
+            Transaction::Void(void) => self.process_void(void).await,
+            Transaction::Dispute(dispute) => self.process_dispute(dispute).await,
+            Transaction::Resolve(resolve) => self.process_resolve(resolve).await,
+            Transaction::Chargeback(chargeback) => self.process_chargeback(chargeback).await,
+            Transaction::Unlock(unlock) => self.process_unlock(unlock).await,
+            Transaction::Freeze(freeze) => self.process_freeze(freeze).await,
+            Transaction::Close(close) => self.process_close(close).await,
+        }
+    }
+
+    /// the reason a client's account refuses this transaction, if it's closed or locked and the
+    /// lock policy forbids it
+    async fn account_restriction(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<Option<RejectionReason>, EngineErrors> {
+        // transfers have to be accepted by the accounts on both ends
+        let parties = std::iter::once(transaction.client()).chain(transaction.counterparty());
+        for id in parties {
+            let client = match self.find_client(&id).await? {
+                Some(client) => client,
+                // clients without any history can't be locked or closed yet
+                None => continue,
+            };
+            if client.closed {
+                return Ok(Some(RejectionReason::AccountClosed));
+            }
+            if client.locked && self.lock_policy.action_for(transaction) == LockAction::Reject {
+                return Ok(Some(RejectionReason::AccountLocked));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn process_deposit(&mut self, deposit: Deposit) -> EngineResult {
+        if let Some(stored) = self.find_transaction(&deposit.tx).await? {
+            return Ok(self.duplicate_policy.outcome_for(
+                &stored,
+                TransactionKind::Deposit,
+                &deposit.client,
+                &deposit.amount,
+            ));
+        }
+
+        self.insert_transaction(StoredTransaction {
+            id: deposit.tx,
+            kind: TransactionKind::Deposit,
+            client: deposit.client,
+            amount: deposit.amount.clone(),
+            status: TransactionStatus::Processed,
+            sequence: self.sequence,
+            timestamp: self.now.timestamp,
+            disputes: 0,
+            disputed: AmountInMinorUnits::default(),
+            charged_back: AmountInMinorUnits::default(),
+            resolved: AmountInMinorUnits::default(),
+            disputed_at: None,
+        })
+        .await?;
+        self.update_client(
+            &deposit.client,
+            ClientUpdate::Deposit {
+                available_increase: deposit.amount.clone(),
+                total_increase: deposit.amount,
+            },
+        )
+        .await?;
+        Ok(TransactionOutcome::Applied)
+    }
+
+    async fn process_withdrawal(&mut self, withdrawal: Withdrawal) -> EngineResult {
+        // ids are unique across deposits and withdrawals, as both can be referenced by disputes
+        if let Some(stored) = self.find_transaction(&withdrawal.tx).await? {
+            return Ok(self.duplicate_policy.outcome_for(
+                &stored,
+                TransactionKind::Withdrawal,
+                &withdrawal.client,
+                &withdrawal.amount,
+            ));
+        }
+
+        let client = match self.find_client(&withdrawal.client).await? {
+            Some(client) => client,
+            // nothing was ever deposited, so there are no funds to withdraw
+            None => {
+                return Ok(TransactionOutcome::Rejected {
+                    reason: RejectionReason::UnknownClient,
+                })
+            }
+        };
+        let fee = self.fee_for(&withdrawal.client, FeeType::Withdrawal, &withdrawal.amount)?;
+        // the client has to be able to cover the fee as well
+        let charged = withdrawal
+            .amount
+            .clone()
+            .checked_add(fee.clone())
+            .ok_or(ClientRepositoryErrors::BalanceOverflow(withdrawal.client))?;
+        if !self.overdraft_policy.allows_withdrawal(&client, &charged) {
+            return Ok(TransactionOutcome::Rejected {
+                reason: RejectionReason::InsufficientFunds,
+            });
+        }
+
+        // withdrawals are stored the same way as deposits so they can be disputed later on
+        self.insert_transaction(StoredTransaction {
+            id: withdrawal.tx,
+            kind: TransactionKind::Withdrawal,
+            client: withdrawal.client,
+            amount: withdrawal.amount.clone(),
+            status: TransactionStatus::Processed,
+            sequence: self.sequence,
+            timestamp: self.now.timestamp,
+            disputes: 0,
+            disputed: AmountInMinorUnits::default(),
+            charged_back: AmountInMinorUnits::default(),
+            resolved: AmountInMinorUnits::default(),
+            disputed_at: None,
+        })
+        .await?;
+        self.update_client(
+            &withdrawal.client,
+            ClientUpdate::Withdrawal {
+                available_decrease: withdrawal.amount.clone(),
+                total_decrease: withdrawal.amount.clone(),
+            },
+        )
+        .await?;
+        self.charge_fee(&withdrawal.client, fee).await?;
+        Ok(TransactionOutcome::Applied)
+    }
+
+    async fn process_transfer(&mut self, transfer: Transfer) -> EngineResult {
+        if let Some(stored) = self.find_transaction(&transfer.tx).await? {
+            return Ok(self.duplicate_policy.outcome_for(
+                &stored,
+                TransactionKind::Transfer { to: transfer.to },
+                &transfer.from,
+                &transfer.amount,
+            ));
+        }
+        if transfer.from == transfer.to {
+            return Ok(TransactionOutcome::Rejected {
+                reason: RejectionReason::SelfTransfer,
+            });
+        }
+
+        let sender = match self.find_client(&transfer.from).await? {
+            Some(client) => client,
+            None => {
+                return Ok(TransactionOutcome::Rejected {
+                    reason: RejectionReason::UnknownClient,
+                })
+            }
+        };
+        if !self
+            .overdraft_policy
+            .allows_withdrawal(&sender, &transfer.amount)
+        {
+            return Ok(TransactionOutcome::Rejected {
+                reason: RejectionReason::InsufficientFunds,
+            });
+        }
+
+        // stored under the sender, who is the one able to dispute it
+        self.insert_transaction(StoredTransaction {
+            id: transfer.tx,
+            kind: TransactionKind::Transfer { to: transfer.to },
+            client: transfer.from,
+            amount: transfer.amount.clone(),
+            status: TransactionStatus::Processed,
+            sequence: self.sequence,
+            timestamp: self.now.timestamp,
+            disputes: 0,
+            disputed: AmountInMinorUnits::default(),
+            charged_back: AmountInMinorUnits::default(),
+            resolved: AmountInMinorUnits::default(),
+            disputed_at: None,
+        })
+        .await?;
+        // both sides are updated in the same unit of work, so a transfer is never half applied
+        self.update_client(
+            &transfer.from,
+            ClientUpdate::Withdrawal {
+                available_decrease: transfer.amount.clone(),
+                total_decrease: transfer.amount.clone(),
+            },
+        )
+        .await?;
+        self.update_client(
+            &transfer.to,
+            ClientUpdate::Deposit {
+                available_increase: transfer.amount.clone(),
+                total_increase: transfer.amount,
+            },
+        )
+        .await?;
+        Ok(TransactionOutcome::Applied)
+    }
+
+    async fn process_authorize(&mut self, authorize: Authorize) -> EngineResult {
+        if let Some(stored) = self.find_transaction(&authorize.tx).await? {
+            return Ok(self.duplicate_policy.outcome_for(
+                &stored,
+                TransactionKind::Authorization,
+                &authorize.client,
+                &authorize.amount,
+            ));
+        }
+
+        let client = match self.find_client(&authorize.client).await? {
+            Some(client) => client,
+            None => {
+                return Ok(TransactionOutcome::Rejected {
+                    reason: RejectionReason::UnknownClient,
+                })
+            }
+        };
+        let fee = self.fee_for(&authorize.client, FeeType::Withdrawal, &authorize.amount)?;
+        // the reserved funds have to be there when the authorization is captured later on,
+        // and so does the fee charged on the capture
+        let charged = authorize
+            .amount
+            .clone()
+            .checked_add(fee)
+            .ok_or(ClientRepositoryErrors::BalanceOverflow(authorize.client))?;
+        if !self.overdraft_policy.allows_withdrawal(&client, &charged) {
+            return Ok(TransactionOutcome::Rejected {
+                reason: RejectionReason::InsufficientFunds,
+            });
+        }
+
+        self.insert_transaction(StoredTransaction {
+            id: authorize.tx,
+            kind: TransactionKind::Authorization,
+            client: authorize.client,
+            amount: authorize.amount.clone(),
+            status: TransactionStatus::Authorized,
+            sequence: self.sequence,
+            timestamp: self.now.timestamp,
+            disputes: 0,
+            disputed: AmountInMinorUnits::default(),
+            charged_back: AmountInMinorUnits::default(),
+            resolved: AmountInMinorUnits::default(),
+            disputed_at: None,
+        })
+        .await?;
+        self.update_client(
+            &authorize.client,
+            ClientUpdate::Authorize {
+                available_decrease: authorize.amount.clone(),
+                held_increase: authorize.amount,
+            },
+        )
+        .await?;
+        if let Some(open_authorizations) = &mut self.open_authorizations {
+            open_authorizations.insert(authorize.tx);
+        }
+        Ok(TransactionOutcome::Applied)
+    }
+
+    async fn process_capture(&mut self, capture: Capture) -> EngineResult {
+        let stored = match self
+            .find_authorization(&capture.client, &capture.tx)
+            .await?
+        {
+            Ok(stored) => stored,
+            Err(outcome) => return Ok(outcome),
+        };
+        let fee = self.fee_for(&capture.client, FeeType::Withdrawal, &stored.amount)?;
+        // the fee was covered when the funds were reserved, but may have been spent since
+        if fee != AmountInMinorUnits::default() {
+            let client = self.clients.get(&capture.client).await?;
+            if !self.overdraft_policy.allows_withdrawal(&client, &fee) {
+                return Ok(TransactionOutcome::Rejected {
+                    reason: RejectionReason::InsufficientFunds,
+                });
+            }
+        }
+
+        // from here on the authorization is a regular withdrawal, which can be disputed
+        self.update_transaction_status(
+            &capture.tx,
+            StatusUpdate {
+                status: TransactionStatus::Processed,
+                disputed: stored.disputed,
+                charged_back: stored.charged_back,
+                resolved: stored.resolved,
+                disputes: stored.disputes,
+                disputed_at: stored.disputed_at,
+            },
+        )
+        .await?;
+        self.update_client(
+            &capture.client,
+            ClientUpdate::Capture {
+                held_decrease: stored.amount.clone(),
+                total_decrease: stored.amount,
+            },
+        )
+        .await?;
+        self.charge_fee(&capture.client, fee).await?;
+        Ok(TransactionOutcome::Applied)
+    }
+
+    async fn process_void(&mut self, void: Void) -> EngineResult {
+        let stored = match self.find_authorization(&void.client, &void.tx).await? {
+            Ok(stored) => stored,
+            Err(outcome) => return Ok(outcome),
+        };
+
+        self.update_transaction_status(
+            &void.tx,
+            StatusUpdate {
+                status: TransactionStatus::Voided,
+                disputed: stored.disputed,
+                charged_back: stored.charged_back,
+                resolved: stored.resolved,
+                disputes: stored.disputes,
+                disputed_at: stored.disputed_at,
+            },
+        )
+        .await?;
+        self.update_client(
+            &void.client,
+            ClientUpdate::Void {
+                held_decrease: stored.amount.clone(),
+                available_increase: stored.amount,
+            },
+        )
+        .await?;
+        Ok(TransactionOutcome::Applied)
+    }
+
+    async fn process_dispute(&mut self, dispute: Dispute) -> EngineResult {
+        let (stored, step) = match self
+            .find_referenced(
+                &dispute.client,
+                &dispute.tx,
+                DisputeEvent::Dispute,
+                dispute.amount.as_ref(),
+            )
+            .await?
+        {
+            Ok(referenced) => referenced,
+            Err(outcome) => return Ok(outcome),
+        };
+        if !self.dispute_window.allows_dispute(&stored, &self.now) {
+            return Ok(TransactionOutcome::Rejected {
+                reason: RejectionReason::DisputeWindowExpired,
+            });
+        }
+
+        let disputes = if step.starts_cycle {
+            stored.disputes + 1
+        } else {
+            stored.disputes
+        };
+        // partial disputes add to the open dispute, which keeps running from when it was opened
+        let disputed_at = if stored.status == TransactionStatus::Disputed {
+            stored.disputed_at
+        } else {
+            Some(self.now)
+        };
+        let amount = step.amount.clone();
+        let (account, update) = match stored.kind {
+            TransactionKind::Deposit => (
+                stored.client,
+                ClientUpdate::Dispute {
+                    available_decrease: amount.clone(),
+                    held_increase: amount,
+                },
+            ),
+            TransactionKind::Withdrawal | TransactionKind::Authorization => (
+                stored.client,
+                ClientUpdate::WithdrawalDispute {
+                    held_increase: amount.clone(),
+                    total_increase: amount,
+                },
+            ),
+            // the recipient's funds are held until the dispute is settled
+            TransactionKind::Transfer { to } => (
+                to,
+                ClientUpdate::Dispute {
+                    available_decrease: amount.clone(),
+                    held_increase: amount,
+                },
+            ),
+        };
+        self.update_transaction_status(
+            &dispute.tx,
+            StatusUpdate {
+                status: step.status,
+                disputed: stored.disputed.clone() + step.amount.clone(),
+                charged_back: stored.charged_back.clone(),
+                resolved: stored.resolved.clone() - step.redisputed.clone(),
+                disputes,
+                disputed_at,
+            },
+        )
+        .await?;
+        self.update_client(&account, update).await?;
+        if let Some(open_disputes) = &mut self.open_disputes {
+            open_disputes.insert(dispute.tx);
+        }
+        Ok(TransactionOutcome::Applied)
+    }
+
+    async fn process_resolve(&mut self, resolve: Resolve) -> EngineResult {
+        let (stored, step) = match self
+            .find_referenced(
+                &resolve.client,
+                &resolve.tx,
+                DisputeEvent::Resolve,
+                resolve.amount.as_ref(),
+            )
+            .await?
+        {
+            Ok(referenced) => referenced,
+            Err(outcome) => return Ok(outcome),
+        };
+
+        let amount = step.amount.clone();
+        let (account, update) = match stored.kind {
+            TransactionKind::Deposit => (
+                stored.client,
+                ClientUpdate::Resolve {
+                    available_increase: amount.clone(),
+                    held_decrease: amount,
+                },
+            ),
+            TransactionKind::Withdrawal | TransactionKind::Authorization => (
+                stored.client,
+                ClientUpdate::WithdrawalResolve {
+                    held_decrease: amount.clone(),
+                    total_decrease: amount,
+                },
+            ),
+            // the transfer stands, the recipient gets to keep the funds
+            TransactionKind::Transfer { to } => (
+                to,
+                ClientUpdate::Resolve {
+                    available_increase: amount.clone(),
+                    held_decrease: amount,
+                },
+            ),
+        };
+        self.update_transaction_status(
+            &resolve.tx,
+            StatusUpdate {
+                status: step.status,
+                disputed: stored.disputed.clone() - step.amount.clone(),
+                charged_back: stored.charged_back.clone(),
+                resolved: stored.resolved.clone() + step.amount.clone(),
+                disputes: stored.disputes,
+                disputed_at: stored.disputed_at,
+            },
+        )
+        .await?;
+        self.update_client(&account, update).await?;
+        Ok(TransactionOutcome::Applied)
+    }
+
     async fn process_chargeback(&mut self, chargeback: Chargeback) -> EngineResult {
-        let state = self
-            .transactions
-            .get_transaction_status(&chargeback.tx)
+        let (stored, step) = match self
+            .find_referenced(
+                &chargeback.client,
+                &chargeback.tx,
+                DisputeEvent::Chargeback,
+                chargeback.amount.as_ref(),
+            )
+            .await?
+        {
+            Ok(referenced) => referenced,
+            Err(outcome) => return Ok(outcome),
+        };
+
+        let amount = step.amount.clone();
+        let (account, update) = match stored.kind {
+            TransactionKind::Deposit => (
+                stored.client,
+                ClientUpdate::Chargeback {
+                    held_decrease: amount.clone(),
+                    total_decrease: amount,
+                },
+            ),
+            TransactionKind::Withdrawal | TransactionKind::Authorization => (
+                stored.client,
+                ClientUpdate::WithdrawalChargeback {
+                    held_decrease: amount.clone(),
+                    available_increase: amount,
+                },
+            ),
+            // the transfer is reversed, which locks the recipient's account like a charged back deposit
+            TransactionKind::Transfer { to } => (
+                to,
+                ClientUpdate::Chargeback {
+                    held_decrease: amount.clone(),
+                    total_decrease: amount,
+                },
+            ),
+        };
+        self.update_transaction_status(
+            &chargeback.tx,
+            StatusUpdate {
+                status: step.status,
+                disputed: stored.disputed.clone() - step.amount.clone(),
+                charged_back: stored.charged_back.clone() + step.amount.clone(),
+                resolved: stored.resolved.clone(),
+                disputes: stored.disputes,
+                disputed_at: stored.disputed_at,
+            },
+        )
+        .await?;
+        let fee = self.fee_for(&chargeback.client, FeeType::Chargeback, &step.amount)?;
+        self.update_client(&account, update).await?;
+        // and the sender gets the funds back
+        if let TransactionKind::Transfer { .. } = stored.kind {
+            self.update_client(
+                &chargeback.client,
+                ClientUpdate::Deposit {
+                    available_increase: step.amount.clone(),
+                    total_increase: step.amount,
+                },
+            )
             .await?;
+        }
+        self.charge_fee(&chargeback.client, fee).await?;
+        Ok(TransactionOutcome::Applied)
+    }
 
-        // only process chargeback if transaction is currently disputed
-        if state == TransactionStatus::Disputed {
-            let amount = self
-                .transactions
-                .get_transaction_value(&chargeback.tx)
-                .await?;
-            self.transactions
-                .store_transaction_status(chargeback.tx, TransactionStatus::ChargedBack)
-                .await?;
-            self.clients
-                .update(
-                    &chargeback.client,
-                    ClientUpdate::Chargeback {
-                        held_decrease: amount.clone(),
-                        total_decrease: amount,
-                    },
-                )
-                .await?;
+    async fn process_unlock(&mut self, unlock: Unlock) -> EngineResult {
+        let client = match self.find_account(&unlock.client).await? {
+            Ok(client) => client,
+            Err(outcome) => return Ok(outcome),
+        };
+        if !client.locked {
+            return Ok(TransactionOutcome::Ignored {
+                reason: RejectionReason::AccountNotLocked,
+            });
         }
+
+        self.update_client(&unlock.client, ClientUpdate::Unlock)
+            .await?;
+        Ok(TransactionOutcome::Applied)
+    }
+
+    async fn process_freeze(&mut self, freeze: Freeze) -> EngineResult {
+        let client = match self.find_account(&freeze.client).await? {
+            Ok(client) => client,
+            Err(outcome) => return Ok(outcome),
+        };
+        if client.locked {
+            return Ok(TransactionOutcome::Ignored {
+                reason: RejectionReason::AccountLocked,
+            });
+        }
+
+        self.update_client(&freeze.client, ClientUpdate::Freeze)
+            .await?;
+        Ok(TransactionOutcome::Applied)
+    }
+
+    async fn process_close(&mut self, close: Close) -> EngineResult {
+        let client = match self.find_account(&close.client).await? {
+            Ok(client) => client,
+            Err(outcome) => return Ok(outcome),
+        };
+        // fees keep being credited to the house account, so it has to stay open
+        if self.fee_schedule.is_house_account(&close.client) {
+            return Ok(TransactionOutcome::Rejected {
+                reason: RejectionReason::HouseAccount,
+            });
+        }
+        // open disputes have to be settled before the account can be paid out
+        if client.held != AmountInMinorUnits::default() {
+            return Ok(TransactionOutcome::Rejected {
+                reason: RejectionReason::FundsHeld,
+            });
+        }
+        // as does an overdraft
+        if client.available < AmountInMinorUnits::default() {
+            return Ok(TransactionOutcome::Rejected {
+                reason: RejectionReason::InsufficientFunds,
+            });
+        }
+        // a disputed transfer holds the funds on the recipient's account, but only the sender can
+        // settle the dispute, which a closed account no longer can
+        if self.has_open_dispute(&close.client).await? {
+            return Ok(TransactionOutcome::Rejected {
+                reason: RejectionReason::DisputeOpen,
+            });
+        }
+
+        self.update_client(
+            &close.client,
+            ClientUpdate::Close {
+                available_decrease: client.available,
+                total_decrease: client.total,
+            },
+        )
+        .await?;
+        Ok(TransactionOutcome::Applied)
+    }
+
+    /// whether any of the client's stored transactions is currently disputed
+    async fn has_open_dispute(&self, client: &ClientId) -> Result<bool, EngineErrors> {
+        let mut transactions = self.transactions.get_all().await?;
+        while let Some(stored) = transactions.try_next().await? {
+            if stored.client == *client && stored.status == TransactionStatus::Disputed {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// looks up the client an account operation applies to, or the outcome to report if there's
+    /// no account to operate on
+    async fn find_account(
+        &self,
+        id: &ClientId,
+    ) -> Result<Result<Client, TransactionOutcome>, EngineErrors> {
+        match self.find_client(id).await? {
+            Some(client) => Ok(Ok(client)),
+            None => Ok(Err(TransactionOutcome::Rejected {
+                reason: RejectionReason::UnknownClient,
+            })),
+        }
+    }
+
+    /// looks up a client, treating clients without any history as a normal business case
+    async fn find_client(&self, id: &ClientId) -> Result<Option<Client>, EngineErrors> {
+        match self.clients.get(id).await {
+            Ok(client) => Ok(Some(client)),
+            Err(ClientRepositoryErrors::ClientNotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// looks up a transaction, treating unknown ids as a normal business case
+    async fn find_transaction(
+        &self,
+        tx: &TransactionId,
+    ) -> Result<Option<StoredTransaction>, EngineErrors> {
+        match self.transactions.get(tx).await {
+            Ok(stored) => Ok(Some(stored)),
+            Err(TransactionRepositoryErrors::TransactionNotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// looks up the authorization referenced by a capture or void, or the outcome to report if it
+    /// belongs to another client or was already captured, voided or expired
+    async fn find_authorization(
+        &self,
+        client: &ClientId,
+        tx: &TransactionId,
+    ) -> Result<Result<StoredTransaction, TransactionOutcome>, EngineErrors> {
+        let stored = match self.find_transaction(tx).await? {
+            Some(stored) => stored,
+            None => {
+                return Ok(Err(TransactionOutcome::Ignored {
+                    reason: RejectionReason::TransactionNotFound,
+                }))
+            }
+        };
+
+        if stored.client != *client {
+            return Ok(Err(TransactionOutcome::Rejected {
+                reason: RejectionReason::ClientMismatch,
+            }));
+        }
+        if stored.status != TransactionStatus::Authorized {
+            return Ok(Err(TransactionOutcome::Ignored {
+                reason: RejectionReason::AuthorizationNotOpen,
+            }));
+        }
+        Ok(Ok(stored))
+    }
+
+    /// looks up the transaction referenced by a dispute-family record along with what `event` does
+    /// to it, or the outcome to report if it belongs to another client or the dispute lifecycle
+    /// doesn't allow `event` in its current state
+    async fn find_referenced(
+        &self,
+        client: &ClientId,
+        tx: &TransactionId,
+        event: DisputeEvent,
+        amount: Option<&AmountInMinorUnits>,
+    ) -> Result<Result<(StoredTransaction, DisputeStep), TransactionOutcome>, EngineErrors> {
+        let stored = match self.find_transaction(tx).await? {
+            Some(stored) => stored,
+            None => {
+                return Ok(Err(TransactionOutcome::Ignored {
+                    reason: RejectionReason::TransactionNotFound,
+                }))
+            }
+        };
+
+        if stored.client != *client {
+            return Ok(Err(TransactionOutcome::Rejected {
+                reason: RejectionReason::ClientMismatch,
+            }));
+        }
+        // disputing a transfer moves funds on the recipient's account, which has to still be open
+        if let TransactionKind::Transfer { to } = stored.kind {
+            if self
+                .find_client(&to)
+                .await?
+                .is_some_and(|recipient| recipient.closed)
+            {
+                return Ok(Err(TransactionOutcome::Rejected {
+                    reason: RejectionReason::AccountClosed,
+                }));
+            }
+        }
+
+        match self.dispute_lifecycle.transition(&stored, event, amount) {
+            Ok(step) => Ok(Ok((stored, step))),
+            // the transaction could be acted on, just not as much as the record asks for
+            Err(
+                reason @ (RejectionReason::DisputeLimitReached
+                | RejectionReason::DisputedAmountExceeded),
+            ) => Ok(Err(TransactionOutcome::Rejected { reason })),
+            Err(reason) => Ok(Err(TransactionOutcome::Ignored { reason })),
+        }
+    }
+
+    /// the fee charged on a transaction of `tx_type` with `amount`, a fee too large to represent
+    /// is reported as an overflow of the client's balance
+    fn fee_for(
+        &self,
+        client: &ClientId,
+        tx_type: FeeType,
+        amount: &AmountInMinorUnits,
+    ) -> Result<AmountInMinorUnits, EngineErrors> {
+        self.fee_schedule
+            .fee_for(tx_type, amount)
+            .ok_or_else(|| ClientRepositoryErrors::BalanceOverflow(*client).into())
+    }
+
+    /// moves `fee` from the client to the house account
+    async fn charge_fee(
+        &mut self,
+        client: &ClientId,
+        fee: AmountInMinorUnits,
+    ) -> Result<(), EngineErrors> {
+        if fee == AmountInMinorUnits::default() {
+            return Ok(());
+        }
+        self.update_client(
+            client,
+            ClientUpdate::Fee {
+                available_decrease: fee.clone(),
+                total_decrease: fee.clone(),
+            },
+        )
+        .await?;
+        let house_account = self.fee_schedule.house_account;
+        self.update_client(
+            &house_account,
+            ClientUpdate::FeeIncome {
+                available_increase: fee.clone(),
+                total_increase: fee,
+            },
+        )
+        .await
+    }
+
+    async fn insert_transaction(
+        &mut self,
+        transaction: StoredTransaction,
+    ) -> Result<(), EngineErrors> {
+        self.transactions.insert(transaction.clone()).await?;
+        self.changes
+            .push(JournaledChange::TransactionInserted(transaction));
+        Ok(())
+    }
+
+    async fn update_transaction_status(
+        &mut self,
+        id: &TransactionId,
+        update: StatusUpdate,
+    ) -> Result<(), EngineErrors> {
+        self.transactions.update_status(id, update.clone()).await?;
+        self.changes
+            .push(JournaledChange::TransactionStatus { id: *id, update });
+        Ok(())
+    }
+
+    async fn update_client(
+        &mut self,
+        id: &ClientId,
+        update: ClientUpdate,
+    ) -> Result<(), EngineErrors> {
+        self.clients.update(id, update.clone()).await?;
+        self.changes
+            .push(JournaledChange::Client { id: *id, update });
+        Ok(())
+    }
+
+    /// appends the applied transaction and the changes it caused to the journal
+    async fn journal_applied(&mut self, transaction: Transaction) -> Result<(), EngineErrors> {
+        let changes = std::mem::take(&mut self.changes);
+        self.journal
+            .append(JournalEntry {
+                sequence: self.sequence,
+                transaction,
+                changes,
+            })
+            .await?;
         Ok(())
     }
 }
 
 #[cfg(test)]
-mod tests;
+pub(crate) mod tests;
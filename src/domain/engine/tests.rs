@@ -1,8 +1,20 @@
 // Primary test modules
+mod account_operations;
+mod authorization;
 mod chargeback;
 mod deposit;
 mod dispute;
+mod dispute_window;
+mod duplicates;
+mod fees;
+mod journal;
+mod locked;
+mod overdraft;
+mod partial_dispute;
 mod resolve;
+mod transfer;
+mod unit_of_work;
 mod withdrawal;
+mod withdrawal_dispute;
 // Test helpers
-mod test_helpers;
+pub(crate) mod test_helpers;
@@ -0,0 +1,250 @@
+use crate::domain::engine::tests::test_helpers::{
+    close, deposit, dispute, freeze, test_client, transfer, unlock, withdraw, TestContext,
+    OTHER_CLIENT_ID, TEST_CLIENT_ID, TEST_TRANSACTION_ID_2, TEST_TRANSACTION_ID_3,
+};
+use crate::domain::model::{
+    AmountInMinorUnits, RejectionReason, Resolve, Transaction, TransactionOutcome,
+};
+use crate::domain::ports::{ClientRepository, Engine};
+
+#[tokio::test]
+async fn unlock_clears_lock_after_chargeback() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_chargeback(
+        AmountInMinorUnits::from(10),
+        AmountInMinorUnits::from(90),
+        AmountInMinorUnits::from(0),
+    )
+    .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(unlock(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(withdraw(TEST_CLIENT_ID, TEST_TRANSACTION_ID_3, 40))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = ctx.get_clients().await;
+    assert!(!clients[0].locked);
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(50));
+}
+
+#[tokio::test]
+async fn unlock_is_ignored_when_account_not_locked() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(unlock(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Ignored {
+            reason: RejectionReason::AccountNotLocked
+        }
+    );
+}
+
+#[tokio::test]
+async fn unlock_of_unknown_client_is_rejected() {
+    // test setup
+    let mut ctx = TestContext::new();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(unlock(OTHER_CLIENT_ID, TEST_TRANSACTION_ID_2))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::UnknownClient
+        }
+    );
+    assert!(ctx.get_clients().await.is_empty());
+}
+
+#[tokio::test]
+async fn freeze_locks_account() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(freeze(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2))
+        .await
+        .unwrap();
+    let deposit = ctx
+        .engine
+        .process_transaction(deposit(TEST_CLIENT_ID, TEST_TRANSACTION_ID_3, 5))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    assert_eq!(
+        deposit,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::AccountLocked
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert!(clients[0].locked);
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(100));
+}
+
+#[tokio::test]
+async fn close_pays_out_available_balance() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(close(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = ctx.get_clients().await;
+    assert!(clients[0].closed);
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(0));
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(0));
+}
+
+#[tokio::test]
+async fn closed_account_rejects_further_transactions() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(close(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(deposit(TEST_CLIENT_ID, TEST_TRANSACTION_ID_3, 5))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::AccountClosed
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(0));
+}
+
+#[tokio::test]
+async fn close_is_rejected_while_funds_are_held() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_amount(AmountInMinorUnits::from(90), AmountInMinorUnits::from(10))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(close(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::FundsHeld
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert!(!clients[0].closed);
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(90));
+}
+
+#[tokio::test]
+async fn close_is_rejected_while_a_sent_transfer_is_disputed() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(10)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(transfer(
+            TEST_CLIENT_ID,
+            OTHER_CLIENT_ID,
+            TEST_TRANSACTION_ID_2,
+            10,
+        ))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2, None))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(close(TEST_CLIENT_ID, TEST_TRANSACTION_ID_3))
+        .await
+        .unwrap();
+    let resolved = ctx
+        .engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_2,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::DisputeOpen
+        }
+    );
+    assert_eq!(resolved, TransactionOutcome::Applied);
+    let recipient = ctx.client_repo.get(&OTHER_CLIENT_ID).await.unwrap();
+    assert_eq!(recipient.held, AmountInMinorUnits::from(0));
+    assert_eq!(recipient.available, AmountInMinorUnits::from(10));
+}
@@ -0,0 +1,302 @@
+use crate::domain::engine::tests::test_helpers::{
+    authorize, capture, deposit, dispute, random_transactions, test_client, void, TestContext,
+    OTHER_CLIENT_ID, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, TEST_TRANSACTION_ID_2,
+    TEST_TRANSACTION_ID_3,
+};
+use crate::domain::model::{
+    AmountInMinorUnits, AuthorizationExpiry, RejectionReason, TransactionId, TransactionOutcome,
+    TransactionStatus,
+};
+use crate::domain::ports::{ClientRepository, Engine, TransactionsRepository};
+
+const AUTHORIZATION_ID: TransactionId = TEST_TRANSACTION_ID_2;
+
+#[tokio::test]
+async fn authorize_moves_funds_from_available_to_held() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(authorize(TEST_CLIENT_ID, AUTHORIZATION_ID, 30))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(70));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(30));
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(100));
+    let stored = ctx.transaction_repo.get(&AUTHORIZATION_ID).await.unwrap();
+    assert_eq!(stored.status, TransactionStatus::Authorized);
+}
+
+#[tokio::test]
+async fn authorize_exceeding_available_funds_is_rejected() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(10)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(authorize(TEST_CLIENT_ID, AUTHORIZATION_ID, 30))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::InsufficientFunds
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(0));
+}
+
+#[tokio::test]
+async fn capture_settles_held_funds() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(authorize(TEST_CLIENT_ID, AUTHORIZATION_ID, 30))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(capture(TEST_CLIENT_ID, AUTHORIZATION_ID))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(70));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(0));
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(70));
+    let stored = ctx.transaction_repo.get(&AUTHORIZATION_ID).await.unwrap();
+    assert_eq!(stored.status, TransactionStatus::Processed);
+}
+
+#[tokio::test]
+async fn void_releases_held_funds() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(authorize(TEST_CLIENT_ID, AUTHORIZATION_ID, 30))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(void(TEST_CLIENT_ID, AUTHORIZATION_ID))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(100));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(0));
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(100));
+}
+
+#[tokio::test]
+async fn capture_of_voided_authorization_is_ignored() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(authorize(TEST_CLIENT_ID, AUTHORIZATION_ID, 30))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(void(TEST_CLIENT_ID, AUTHORIZATION_ID))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(capture(TEST_CLIENT_ID, AUTHORIZATION_ID))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Ignored {
+            reason: RejectionReason::AuthorizationNotOpen
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(100));
+}
+
+#[tokio::test]
+async fn capture_of_deposit_is_ignored() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(capture(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Ignored {
+            reason: RejectionReason::AuthorizationNotOpen
+        }
+    );
+}
+
+#[tokio::test]
+async fn authorization_is_voided_once_expired() {
+    // test setup
+    let mut ctx = TestContext::new().with_authorization_expiry(AuthorizationExpiry::new(Some(2)));
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(authorize(TEST_CLIENT_ID, AUTHORIZATION_ID, 30))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(deposit(OTHER_CLIENT_ID, TEST_TRANSACTION_ID_3, 5))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(capture(TEST_CLIENT_ID, AUTHORIZATION_ID))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Ignored {
+            reason: RejectionReason::AuthorizationNotOpen
+        }
+    );
+    let stored = ctx.transaction_repo.get(&AUTHORIZATION_ID).await.unwrap();
+    assert_eq!(stored.status, TransactionStatus::Voided);
+    let client = ctx.client_repo.get(&TEST_CLIENT_ID).await.unwrap();
+    assert_eq!(client.available, AmountInMinorUnits::from(100));
+    assert_eq!(client.held, AmountInMinorUnits::from(0));
+}
+
+#[tokio::test]
+async fn captured_authorization_is_disputed_like_a_withdrawal() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(authorize(TEST_CLIENT_ID, AUTHORIZATION_ID, 30))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(capture(TEST_CLIENT_ID, AUTHORIZATION_ID))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(dispute(TEST_CLIENT_ID, AUTHORIZATION_ID, None))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(70));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(30));
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(100));
+}
+
+#[tokio::test]
+async fn open_authorization_cannot_be_disputed() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(authorize(TEST_CLIENT_ID, AUTHORIZATION_ID, 30))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(dispute(TEST_CLIENT_ID, AUTHORIZATION_ID, None))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Ignored {
+            reason: RejectionReason::TransactionNotDisputable
+        }
+    );
+}
+
+#[tokio::test]
+async fn total_is_always_available_plus_held() {
+    // test setup
+    let mut ctx = TestContext::new().with_authorization_expiry(AuthorizationExpiry::new(Some(50)));
+
+    for (position, transaction) in random_transactions(3, 5_000).into_iter().enumerate() {
+        // test subject
+        ctx.engine.process_transaction(transaction).await.unwrap();
+
+        // check results
+        for client in ctx.get_clients().await {
+            assert_eq!(
+                client.total,
+                client.available.clone() + client.held.clone(),
+                "client {:?} after record {}",
+                client.id,
+                position + 1
+            );
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use crate::domain::engine::tests::test_helpers::{
-    TestContext, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
+    TestContext, OTHER_CLIENT_ID, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
+};
+use crate::domain::model::{
+    AmountInMinorUnits, Chargeback, RejectionReason, Transaction, TransactionOutcome,
 };
-use crate::domain::model::{AmountInMinorUnits, Chargeback, Transaction};
 use crate::domain::ports::Engine;
 
 #[tokio::test]
@@ -18,6 +20,7 @@ async fn chargeback_reduces_held_funds_by_disputed_amount() {
         .process_transaction(Transaction::Chargeback(Chargeback {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -41,6 +44,7 @@ async fn chargeback_reduces_total_funds_by_disputed_amount() {
         .process_transaction(Transaction::Chargeback(Chargeback {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -64,13 +68,14 @@ async fn chargeback_locks_client_account() {
         .process_transaction(Transaction::Chargeback(Chargeback {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
 
     // check results
     let clients = ctx.get_clients().await;
-    assert_eq!(clients[0].locked, true);
+    assert!(clients[0].locked);
 }
 
 #[tokio::test]
@@ -87,6 +92,7 @@ async fn chargeback_does_not_decrease_held_funds_if_transaction_not_disputed() {
         .process_transaction(Transaction::Chargeback(Chargeback {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -110,6 +116,7 @@ async fn chargeback_does_not_decrease_total_funds_if_transaction_not_disputed()
         .process_transaction(Transaction::Chargeback(Chargeback {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -138,6 +145,7 @@ async fn chargeback_does_not_decrease_held_funds_if_transaction_already_charged_
         .process_transaction(Transaction::Chargeback(Chargeback {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -166,6 +174,7 @@ async fn chargeback_does_not_decrease_total_funds_if_transaction_already_charged
         .process_transaction(Transaction::Chargeback(Chargeback {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -194,6 +203,7 @@ async fn chargeback_does_not_decrease_held_funds_if_transaction_already_resolved
         .process_transaction(Transaction::Chargeback(Chargeback {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -222,6 +232,7 @@ async fn chargeback_does_not_decrease_total_funds_if_transaction_already_resolve
         .process_transaction(Transaction::Chargeback(Chargeback {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -230,3 +241,88 @@ async fn chargeback_does_not_decrease_total_funds_if_transaction_already_resolve
     let clients = ctx.get_clients().await;
     assert_eq!(clients[0].total, available_amount + held_amount);
 }
+
+#[tokio::test]
+async fn chargeback_is_reported_as_applied_when_transaction_disputed() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_amount(AmountInMinorUnits::from(100), AmountInMinorUnits::from(50))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+}
+
+#[tokio::test]
+async fn chargeback_is_reported_as_ignored_when_transaction_already_charged_back() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_chargeback(
+        AmountInMinorUnits::from(50),
+        AmountInMinorUnits::from(100),
+        AmountInMinorUnits::from(0),
+    )
+    .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Ignored {
+            reason: RejectionReason::TransactionNotDisputed
+        }
+    );
+}
+
+#[tokio::test]
+async fn chargeback_is_rejected_when_transaction_belongs_to_another_client() {
+    // test setup
+    let disputed_amount = AmountInMinorUnits::from(50);
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_amount(AmountInMinorUnits::from(100), disputed_amount.clone())
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: OTHER_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::ClientMismatch
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients.len(), 1);
+    assert!(!clients[0].locked);
+    assert_eq!(clients[0].held, disputed_amount);
+}
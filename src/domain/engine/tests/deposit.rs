@@ -1,8 +1,11 @@
 use crate::domain::engine::tests::test_helpers::{
-    TestContext, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
+    TestContext, OTHER_CLIENT_ID, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
 };
-use crate::domain::model::{AmountInMinorUnits, Deposit, Transaction};
-use crate::domain::ports::Engine;
+use crate::domain::model::{
+    AmountInMinorUnits, Deposit, RejectionReason, StoredTransaction, Transaction, TransactionId,
+    TransactionKind, TransactionOutcome, TransactionStatus,
+};
+use crate::domain::ports::{Engine, TransactionsRepository};
 
 #[tokio::test]
 async fn deposit_increases_client_available_funds_by_deposit_amount() {
@@ -91,3 +94,155 @@ async fn deposit_does_not_increase_total_funds_if_already_processed() {
     let clients = ctx.get_clients().await;
     assert_eq!(clients[0].total, deposit_amount);
 }
+
+#[tokio::test]
+async fn deposit_is_reported_as_applied() {
+    // test setup
+    let mut ctx = TestContext::new();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(5),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+}
+
+#[tokio::test]
+async fn deposit_is_reported_as_rejected_duplicate_if_already_processed() {
+    // test setup
+    let deposit_amount = AmountInMinorUnits::from(100);
+    let mut ctx = TestContext::new();
+
+    ctx.with_deposit(deposit_amount.clone(), AmountInMinorUnits::from(0))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: deposit_amount,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::DuplicateTransaction
+        }
+    );
+}
+
+#[tokio::test]
+async fn deposit_is_stored_as_a_single_transaction_record() {
+    // test setup
+    let mut ctx = TestContext::new();
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(5),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    assert_eq!(
+        stored,
+        StoredTransaction {
+            id: TEST_TRANSACTION_ID_1,
+            kind: TransactionKind::Deposit,
+            client: TEST_CLIENT_ID,
+            amount: AmountInMinorUnits::from(5),
+            status: TransactionStatus::Processed,
+            sequence: 1,
+            timestamp: None,
+            disputes: 0,
+            disputed: AmountInMinorUnits::default(),
+            charged_back: AmountInMinorUnits::default(),
+            resolved: AmountInMinorUnits::default(),
+            disputed_at: None,
+        }
+    );
+}
+
+#[tokio::test]
+async fn deposit_overflowing_client_balance_is_rejected() {
+    // test setup
+    let large_amount: AmountInMinorUnits = "900000000000000".parse().unwrap();
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(large_amount.clone(), AmountInMinorUnits::from(0))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TransactionId(2),
+            amount: large_amount.clone(),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::BalanceOverflow
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, large_amount);
+    // the deposit wasn't recorded either, so it can't be disputed later
+    assert!(ctx.transaction_repo.get(&TransactionId(2)).await.is_err());
+}
+
+#[tokio::test]
+async fn deposit_overflowing_one_client_does_not_affect_other_clients() {
+    // test setup
+    let large_amount: AmountInMinorUnits = "900000000000000".parse().unwrap();
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(large_amount.clone(), AmountInMinorUnits::from(0))
+        .await;
+    ctx.engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TransactionId(2),
+            amount: large_amount,
+        }))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: OTHER_CLIENT_ID,
+            tx: TransactionId(3),
+            amount: AmountInMinorUnits::from(5),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+}
@@ -1,7 +1,10 @@
 use crate::domain::engine::tests::test_helpers::{
-    test_client, TestContext, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
+    test_client, test_deposit, TestContext, OTHER_CLIENT_ID, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
+};
+use crate::domain::model::{
+    AmountInMinorUnits, Dispute, DisputeLifecycle, RejectionReason, Resolve, Transaction,
+    TransactionOutcome, TransactionStatus,
 };
-use crate::domain::model::{AmountInMinorUnits, Dispute, Transaction, TransactionStatus};
 use crate::domain::ports::{ClientRepository, Engine, TransactionsRepository};
 
 #[tokio::test]
@@ -13,11 +16,10 @@ async fn dispute_reduces_available_funds_by_transaction_amount() {
         .await
         .unwrap();
     ctx.transaction_repo
-        .store_transaction_status(TEST_TRANSACTION_ID_1, TransactionStatus::Processed)
-        .await
-        .unwrap();
-    ctx.transaction_repo
-        .store_transaction_value(TEST_TRANSACTION_ID_1, AmountInMinorUnits::from(100))
+        .insert(test_deposit(
+            AmountInMinorUnits::from(100),
+            TransactionStatus::Processed,
+        ))
         .await
         .unwrap();
 
@@ -26,6 +28,7 @@ async fn dispute_reduces_available_funds_by_transaction_amount() {
         .process_transaction(Transaction::Dispute(Dispute {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -44,11 +47,10 @@ async fn dispute_increases_held_funds_by_transaction_amount() {
         .await
         .unwrap();
     ctx.transaction_repo
-        .store_transaction_status(TEST_TRANSACTION_ID_1, TransactionStatus::Processed)
-        .await
-        .unwrap();
-    ctx.transaction_repo
-        .store_transaction_value(TEST_TRANSACTION_ID_1, AmountInMinorUnits::from(100))
+        .insert(test_deposit(
+            AmountInMinorUnits::from(100),
+            TransactionStatus::Processed,
+        ))
         .await
         .unwrap();
 
@@ -57,6 +59,7 @@ async fn dispute_increases_held_funds_by_transaction_amount() {
         .process_transaction(Transaction::Dispute(Dispute {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -75,11 +78,10 @@ async fn dispute_does_not_change_total_funds() {
         .await
         .unwrap();
     ctx.transaction_repo
-        .store_transaction_status(TEST_TRANSACTION_ID_1, TransactionStatus::Processed)
-        .await
-        .unwrap();
-    ctx.transaction_repo
-        .store_transaction_value(TEST_TRANSACTION_ID_1, AmountInMinorUnits::from(100))
+        .insert(test_deposit(
+            AmountInMinorUnits::from(100),
+            TransactionStatus::Processed,
+        ))
         .await
         .unwrap();
 
@@ -88,6 +90,7 @@ async fn dispute_does_not_change_total_funds() {
         .process_transaction(Transaction::Dispute(Dispute {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -110,17 +113,18 @@ async fn dispute_changes_transaction_status() {
         .process_transaction(Transaction::Dispute(Dispute {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
 
     // check results
-    let status = ctx
+    let stored = ctx
         .transaction_repo
-        .get_transaction_status(&TEST_TRANSACTION_ID_1)
+        .get(&TEST_TRANSACTION_ID_1)
         .await
         .unwrap();
-    assert_eq!(status, TransactionStatus::Disputed)
+    assert_eq!(stored.status, TransactionStatus::Disputed)
 }
 
 #[tokio::test]
@@ -137,6 +141,7 @@ async fn dispute_does_not_change_available_funds_if_txn_already_disputed() {
         .process_transaction(Transaction::Dispute(Dispute {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -161,6 +166,7 @@ async fn dispute_does_not_change_held_funds_if_txn_already_disputed() {
         .process_transaction(Transaction::Dispute(Dispute {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -169,3 +175,178 @@ async fn dispute_does_not_change_held_funds_if_txn_already_disputed() {
     let clients = ctx.get_clients().await;
     assert_eq!(clients[0].held, disputed_amount)
 }
+
+#[tokio::test]
+async fn dispute_is_reported_as_ignored_if_txn_not_found() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(1000)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Ignored {
+            reason: RejectionReason::TransactionNotFound
+        }
+    )
+}
+
+#[tokio::test]
+async fn dispute_is_reported_as_ignored_if_txn_already_disputed() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_amount(AmountInMinorUnits::from(100), AmountInMinorUnits::from(50))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Ignored {
+            reason: RejectionReason::TransactionNotDisputable
+        }
+    )
+}
+
+#[tokio::test]
+async fn dispute_is_rejected_if_txn_belongs_to_another_client() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: OTHER_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::ClientMismatch
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(0));
+}
+
+#[tokio::test]
+async fn resolved_txn_can_be_disputed_again_within_dispute_limit() {
+    // test setup
+    let mut ctx = TestContext::new().with_dispute_lifecycle(DisputeLifecycle::new(2));
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.transaction_repo
+        .insert(test_deposit(
+            AmountInMinorUnits::from(40),
+            TransactionStatus::Resolved,
+        ))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    assert_eq!(stored.status, TransactionStatus::Disputed);
+    assert_eq!(stored.disputes, 2);
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(60));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(40));
+}
+
+#[tokio::test]
+async fn resolved_txn_is_rejected_once_dispute_limit_is_reached() {
+    // test setup
+    let mut ctx = TestContext::new().with_dispute_lifecycle(DisputeLifecycle::new(2));
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+    for _ in 0..2 {
+        ctx.engine
+            .process_transaction(Transaction::Dispute(Dispute {
+                client: TEST_CLIENT_ID,
+                tx: TEST_TRANSACTION_ID_1,
+                amount: None,
+            }))
+            .await
+            .unwrap();
+        ctx.engine
+            .process_transaction(Transaction::Resolve(Resolve {
+                client: TEST_CLIENT_ID,
+                tx: TEST_TRANSACTION_ID_1,
+                amount: None,
+            }))
+            .await
+            .unwrap();
+    }
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::DisputeLimitReached
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(100));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(0));
+}
@@ -0,0 +1,239 @@
+use crate::domain::engine::tests::test_helpers::{
+    deposit, dispute, TestContext, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, TEST_TRANSACTION_ID_2,
+    TEST_TRANSACTION_ID_3,
+};
+use crate::domain::model::{
+    AmountInMinorUnits, Chargeback, DisputeWindow, RejectionReason, TimedTransaction, Timestamp,
+    Transaction, TransactionOutcome, TransactionStatus,
+};
+use crate::domain::ports::{Engine, TransactionsRepository};
+
+fn at(transaction: Transaction, timestamp: u64) -> TimedTransaction {
+    TimedTransaction {
+        timestamp: Some(Timestamp(timestamp)),
+        ..transaction.into()
+    }
+}
+
+#[tokio::test]
+async fn dispute_within_window_is_applied() {
+    // test setup
+    let mut ctx = TestContext::new().with_dispute_window(DisputeWindow::new(Some(2), None));
+    ctx.engine
+        .process_transaction(deposit(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, 10))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(deposit(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2, 10))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, None))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+}
+
+#[tokio::test]
+async fn dispute_is_rejected_once_window_expired_by_record_count() {
+    // test setup
+    let mut ctx = TestContext::new().with_dispute_window(DisputeWindow::new(Some(1), None));
+    ctx.engine
+        .process_transaction(deposit(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, 10))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(deposit(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2, 10))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, None))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::DisputeWindowExpired
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(0));
+}
+
+#[tokio::test]
+async fn dispute_is_rejected_once_window_expired_by_timestamp() {
+    // test setup
+    let mut ctx = TestContext::new().with_dispute_window(DisputeWindow::new(Some(60), None));
+    ctx.engine
+        .process_timed_transaction(at(
+            deposit(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, 10),
+            1_000,
+        ))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_timed_transaction(at(
+            dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, None),
+            1_061,
+        ))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::DisputeWindowExpired
+        }
+    );
+}
+
+#[tokio::test]
+async fn timestamp_is_stored_with_the_transaction() {
+    // test setup
+    let mut ctx = TestContext::new();
+
+    // test subject
+    ctx.engine
+        .process_timed_transaction(at(
+            deposit(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, 10),
+            1_000,
+        ))
+        .await
+        .unwrap();
+
+    // check results
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    assert_eq!(stored.timestamp, Some(Timestamp(1_000)));
+}
+
+#[tokio::test]
+async fn overdue_dispute_is_resolved_before_the_next_record() {
+    // test setup
+    let mut ctx = TestContext::new().with_dispute_window(DisputeWindow::new(None, Some(60)));
+    ctx.engine
+        .process_timed_transaction(at(
+            deposit(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, 10),
+            1_000,
+        ))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_timed_transaction(at(
+            dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, None),
+            1_010,
+        ))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_timed_transaction(at(
+            Transaction::Chargeback(Chargeback {
+                client: TEST_CLIENT_ID,
+                tx: TEST_TRANSACTION_ID_1,
+                amount: None,
+            }),
+            1_070,
+        ))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Ignored {
+            reason: RejectionReason::TransactionNotDisputed
+        }
+    );
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    assert_eq!(stored.status, TransactionStatus::Resolved);
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(10));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(0));
+}
+
+#[tokio::test]
+async fn dispute_within_deadline_stays_open() {
+    // test setup
+    let mut ctx = TestContext::new().with_dispute_window(DisputeWindow::new(None, Some(60)));
+    ctx.engine
+        .process_timed_transaction(at(
+            deposit(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, 10),
+            1_000,
+        ))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_timed_transaction(at(
+            dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, None),
+            1_010,
+        ))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_timed_transaction(at(
+            Transaction::Chargeback(Chargeback {
+                client: TEST_CLIENT_ID,
+                tx: TEST_TRANSACTION_ID_1,
+                amount: None,
+            }),
+            1_069,
+        ))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+}
+
+#[tokio::test]
+async fn dispute_restored_from_repository_is_resolved_once_overdue() {
+    // test setup
+    let mut ctx = TestContext::new().with_dispute_window(DisputeWindow::new(None, Some(2)));
+    ctx.with_disputed_amount(AmountInMinorUnits::from(0), AmountInMinorUnits::from(10))
+        .await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(deposit(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2, 10))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(deposit(TEST_CLIENT_ID, TEST_TRANSACTION_ID_3, 10))
+        .await
+        .unwrap();
+
+    // check results
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    assert_eq!(stored.status, TransactionStatus::Resolved);
+}
@@ -0,0 +1,150 @@
+use crate::domain::engine::tests::test_helpers::{
+    TestContext, OTHER_CLIENT_ID, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
+};
+use crate::domain::model::{
+    AmountInMinorUnits, Deposit, DuplicatePolicy, RejectionReason, Transaction, TransactionOutcome,
+    Withdrawal,
+};
+use crate::domain::ports::Engine;
+
+#[tokio::test]
+async fn withdrawal_reusing_a_deposit_id_is_rejected() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Withdrawal(Withdrawal {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(10),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(100));
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::DuplicateTransaction
+        }
+    );
+}
+
+#[tokio::test]
+async fn deposit_reusing_a_withdrawal_id_is_rejected() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_withdrawal(AmountInMinorUnits::from(90), AmountInMinorUnits::from(10))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(10),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(90));
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::DuplicateTransaction
+        }
+    );
+}
+
+#[tokio::test]
+async fn exact_retry_is_ignored_when_idempotent() {
+    // test setup
+    let mut ctx = TestContext::new().with_duplicate_policy(DuplicatePolicy::Idempotent);
+    ctx.with_withdrawal(AmountInMinorUnits::from(90), AmountInMinorUnits::from(10))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Withdrawal(Withdrawal {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(10),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(90));
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Ignored {
+            reason: RejectionReason::DuplicateTransaction
+        }
+    );
+}
+
+#[tokio::test]
+async fn retry_with_a_different_amount_is_rejected_when_idempotent() {
+    // test setup
+    let mut ctx = TestContext::new().with_duplicate_policy(DuplicatePolicy::Idempotent);
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(50),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::DuplicateTransaction
+        }
+    );
+}
+
+#[tokio::test]
+async fn retry_from_a_different_client_is_rejected_when_idempotent() {
+    // test setup
+    let mut ctx = TestContext::new().with_duplicate_policy(DuplicatePolicy::Idempotent);
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: OTHER_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(100),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(ctx.get_clients().await.len(), 1);
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::DuplicateTransaction
+        }
+    );
+}
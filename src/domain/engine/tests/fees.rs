@@ -0,0 +1,338 @@
+use crate::adapters::file::read_fee_schedule;
+use crate::domain::engine::tests::test_helpers::{
+    authorize, capture, close, flat_fee, test_client, withdraw, TestContext, HOUSE_ACCOUNT,
+    OTHER_CLIENT_ID, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, TEST_TRANSACTION_ID_2,
+    TEST_TRANSACTION_ID_3,
+};
+use crate::domain::model::{
+    AmountInMinorUnits, Chargeback, Client, ClientId, FeeType, RejectionReason, Transaction,
+    TransactionOutcome,
+};
+use crate::domain::ports::{ClientRepository, Engine};
+use std::fs;
+
+async fn client(ctx: &TestContext, id: ClientId) -> Client {
+    ctx.client_repo.get(&id).await.unwrap()
+}
+
+#[tokio::test]
+async fn withdrawal_fee_is_credited_to_house_account() {
+    // test setup
+    let mut ctx = TestContext::new().with_fee_schedule(flat_fee(FeeType::Withdrawal, 2));
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(withdraw(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2, 50))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let charged = client(&ctx, TEST_CLIENT_ID).await;
+    assert_eq!(charged.available, AmountInMinorUnits::from(48));
+    assert_eq!(charged.total, AmountInMinorUnits::from(48));
+    let house = client(&ctx, HOUSE_ACCOUNT).await;
+    assert_eq!(house.available, AmountInMinorUnits::from(2));
+    assert_eq!(house.total, AmountInMinorUnits::from(2));
+}
+
+#[tokio::test]
+async fn withdrawal_is_rejected_when_fee_is_not_covered() {
+    // test setup
+    let mut ctx = TestContext::new().with_fee_schedule(flat_fee(FeeType::Withdrawal, 2));
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(withdraw(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2, 99))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::InsufficientFunds
+        }
+    );
+    assert_eq!(
+        client(&ctx, TEST_CLIENT_ID).await.available,
+        AmountInMinorUnits::from(100)
+    );
+    assert_eq!(ctx.get_clients().await.len(), 1);
+}
+
+#[tokio::test]
+async fn chargeback_fee_is_charged_to_disputing_client() {
+    // test setup
+    let mut ctx = TestContext::new().with_fee_schedule(flat_fee(FeeType::Chargeback, 15));
+    ctx.with_disputed_amount(AmountInMinorUnits::from(90), AmountInMinorUnits::from(10))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let charged = client(&ctx, TEST_CLIENT_ID).await;
+    assert_eq!(charged.available, AmountInMinorUnits::from(75));
+    assert_eq!(charged.held, AmountInMinorUnits::from(0));
+    assert_eq!(charged.total, AmountInMinorUnits::from(75));
+    assert_eq!(
+        client(&ctx, HOUSE_ACCOUNT).await.total,
+        AmountInMinorUnits::from(15)
+    );
+}
+
+#[tokio::test]
+async fn fees_are_only_charged_on_scheduled_transaction_types() {
+    // test setup
+    let mut ctx = TestContext::new().with_fee_schedule(flat_fee(FeeType::Chargeback, 15));
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    ctx.engine
+        .process_transaction(withdraw(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2, 50))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(50));
+}
+
+#[tokio::test]
+async fn chargeback_fee_is_charged_beyond_the_overdraft_limit() {
+    // test setup
+    let mut ctx = TestContext::new().with_fee_schedule(flat_fee(FeeType::Chargeback, 15));
+    ctx.with_disputed_amount(AmountInMinorUnits::from(5), AmountInMinorUnits::from(10))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let charged = client(&ctx, TEST_CLIENT_ID).await;
+    assert_eq!(
+        charged.available,
+        AmountInMinorUnits::from(0) - AmountInMinorUnits::from(10)
+    );
+    assert_eq!(
+        charged.total,
+        AmountInMinorUnits::from(0) - AmountInMinorUnits::from(10)
+    );
+    assert_eq!(
+        client(&ctx, HOUSE_ACCOUNT).await.total,
+        AmountInMinorUnits::from(15)
+    );
+}
+
+#[tokio::test]
+async fn captured_authorization_is_charged_the_withdrawal_fee() {
+    // test setup
+    let mut ctx = TestContext::new().with_fee_schedule(flat_fee(FeeType::Withdrawal, 2));
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(authorize(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2, 50))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(capture(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let charged = client(&ctx, TEST_CLIENT_ID).await;
+    assert_eq!(charged.available, AmountInMinorUnits::from(48));
+    assert_eq!(charged.held, AmountInMinorUnits::from(0));
+    assert_eq!(charged.total, AmountInMinorUnits::from(48));
+    assert_eq!(
+        client(&ctx, HOUSE_ACCOUNT).await.total,
+        AmountInMinorUnits::from(2)
+    );
+}
+
+#[tokio::test]
+async fn authorization_is_rejected_when_fee_is_not_covered() {
+    // test setup
+    let mut ctx = TestContext::new().with_fee_schedule(flat_fee(FeeType::Withdrawal, 2));
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(authorize(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2, 99))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::InsufficientFunds
+        }
+    );
+    assert_eq!(
+        client(&ctx, TEST_CLIENT_ID).await.held,
+        AmountInMinorUnits::from(0)
+    );
+}
+
+#[tokio::test]
+async fn capture_is_rejected_when_fee_was_spent_since_the_authorization() {
+    // test setup
+    let mut ctx = TestContext::new().with_fee_schedule(flat_fee(FeeType::Withdrawal, 2));
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(authorize(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2, 50))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(withdraw(TEST_CLIENT_ID, TEST_TRANSACTION_ID_3, 48))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(capture(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::InsufficientFunds
+        }
+    );
+    let charged = client(&ctx, TEST_CLIENT_ID).await;
+    assert_eq!(charged.available, AmountInMinorUnits::from(0));
+    assert_eq!(charged.held, AmountInMinorUnits::from(50));
+}
+
+#[tokio::test]
+async fn house_account_cannot_be_closed() {
+    // test setup
+    let mut ctx = TestContext::new().with_fee_schedule(flat_fee(FeeType::Withdrawal, 2));
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(withdraw(TEST_CLIENT_ID, TEST_TRANSACTION_ID_2, 10))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(close(HOUSE_ACCOUNT, TEST_TRANSACTION_ID_3))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::HouseAccount
+        }
+    );
+    let house = client(&ctx, HOUSE_ACCOUNT).await;
+    assert!(!house.closed);
+    assert_eq!(house.total, AmountInMinorUnits::from(2));
+}
+
+#[tokio::test]
+async fn fee_schedule_is_read_from_csv_file() {
+    // test setup
+    let path =
+        std::env::temp_dir().join(format!("payments-engine-fees-{}.csv", std::process::id()));
+    fs::write(
+        &path,
+        "type, from, flat, percent, min, max\nwithdrawal, , 0.5, , ,\nwithdrawal, 100, , 1, 2, 5\n",
+    )
+    .unwrap();
+
+    // test subject
+    let schedule = read_fee_schedule(&path, OTHER_CLIENT_ID);
+
+    // check results
+    fs::remove_file(&path).unwrap();
+    let schedule = schedule.unwrap();
+    assert_eq!(schedule.house_account, OTHER_CLIENT_ID);
+    let fee_for = |amount: u64| {
+        schedule
+            .fee_for(FeeType::Withdrawal, &AmountInMinorUnits::from(amount))
+            .unwrap()
+    };
+    assert_eq!(fee_for(10), "0.5".parse::<AmountInMinorUnits>().unwrap());
+    // 1% of 150 is below the tier's minimum, 1% of 800 above its maximum
+    assert_eq!(fee_for(150), AmountInMinorUnits::from(2));
+    assert_eq!(fee_for(300), AmountInMinorUnits::from(3));
+    assert_eq!(fee_for(800), AmountInMinorUnits::from(5));
+    assert_eq!(
+        schedule.fee_for(FeeType::Chargeback, &AmountInMinorUnits::from(800)),
+        Some(AmountInMinorUnits::default())
+    );
+}
+
+#[tokio::test]
+async fn fee_schedule_with_minimum_above_maximum_is_refused() {
+    // test setup
+    let path = std::env::temp_dir().join(format!(
+        "payments-engine-fees-invalid-{}.csv",
+        std::process::id()
+    ));
+    fs::write(&path, "type, min, max\nchargeback, 10, 5\n").unwrap();
+
+    // test subject
+    let schedule = read_fee_schedule(&path, OTHER_CLIENT_ID);
+
+    // check results
+    fs::remove_file(&path).unwrap();
+    assert!(schedule.is_err());
+}
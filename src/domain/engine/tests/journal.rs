@@ -0,0 +1,123 @@
+use crate::adapters::memory::{
+    InMemoryClientRepository, InMemoryJournalRepository, InMemoryTransactionRepository,
+};
+use crate::domain::engine::tests::test_helpers::{
+    test_client, FailingClientUpdateDeps, FailingClientUpdateRepository, TestContext,
+    TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
+};
+use crate::domain::engine::TransactionEngine;
+use crate::domain::model::{
+    AmountInMinorUnits, Deposit, StoredTransaction, Transaction, TransactionKind,
+    TransactionStatus, Withdrawal,
+};
+use crate::domain::ports::{
+    ClientRepository, ClientUpdate, Engine, JournalEntry, JournalRepository, JournaledChange,
+};
+use futures::TryStreamExt;
+
+async fn journal_entries(journal: &InMemoryJournalRepository) -> Vec<JournalEntry> {
+    journal
+        .get_all()
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn applied_transaction_is_journaled_with_its_changes() {
+    // test setup
+    let mut ctx = TestContext::new();
+    let deposit = Transaction::Deposit(Deposit {
+        client: TEST_CLIENT_ID,
+        tx: TEST_TRANSACTION_ID_1,
+        amount: AmountInMinorUnits::from(5),
+    });
+
+    // test subject
+    ctx.engine
+        .process_transaction(deposit.clone())
+        .await
+        .unwrap();
+
+    // check results
+    let entries = journal_entries(&ctx.journal_repo).await;
+    assert_eq!(
+        entries,
+        vec![JournalEntry {
+            sequence: 1,
+            transaction: deposit,
+            changes: vec![
+                JournaledChange::TransactionInserted(StoredTransaction {
+                    id: TEST_TRANSACTION_ID_1,
+                    kind: TransactionKind::Deposit,
+                    client: TEST_CLIENT_ID,
+                    amount: AmountInMinorUnits::from(5),
+                    status: TransactionStatus::Processed,
+                    sequence: 1,
+                    timestamp: None,
+                    disputes: 0,
+                    disputed: AmountInMinorUnits::default(),
+                    charged_back: AmountInMinorUnits::default(),
+                    resolved: AmountInMinorUnits::default(),
+                    disputed_at: None,
+                }),
+                JournaledChange::Client {
+                    id: TEST_CLIENT_ID,
+                    update: ClientUpdate::Deposit {
+                        available_increase: AmountInMinorUnits::from(5),
+                        total_increase: AmountInMinorUnits::from(5),
+                    },
+                },
+            ],
+        }]
+    );
+}
+
+#[tokio::test]
+async fn rejected_transaction_is_not_journaled() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject - attempt to withdraw more than the available amount of funds
+    ctx.engine
+        .process_transaction(Transaction::Withdrawal(Withdrawal {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(110),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert!(journal_entries(&ctx.journal_repo).await.is_empty());
+}
+
+#[tokio::test]
+async fn failed_transaction_is_not_journaled() {
+    // test setup
+    let journal_repo = InMemoryJournalRepository::default();
+    let mut engine = TransactionEngine::<FailingClientUpdateDeps>::new(
+        FailingClientUpdateRepository(InMemoryClientRepository::default()),
+        InMemoryTransactionRepository::default(),
+        journal_repo.clone(),
+    );
+
+    // test subject
+    let result = engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(5),
+        }))
+        .await;
+
+    // check results
+    assert!(result.is_err());
+    assert!(journal_entries(&journal_repo).await.is_empty());
+}
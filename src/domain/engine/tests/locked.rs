@@ -0,0 +1,315 @@
+use crate::domain::engine::tests::test_helpers::{
+    test_client, TestContext, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, TEST_TRANSACTION_ID_2,
+};
+use crate::domain::model::{
+    AmountInMinorUnits, Chargeback, Deposit, Dispute, LockAction, LockPolicy, RejectionReason,
+    Resolve, Transaction, TransactionOutcome, Withdrawal,
+};
+use crate::domain::ports::{ClientRepository, Engine};
+
+#[tokio::test]
+async fn locked_account_rejects_deposit_by_default() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.lock_test_client().await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_2,
+            amount: AmountInMinorUnits::from(5),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(100));
+}
+
+#[tokio::test]
+async fn locked_account_accepts_deposit_when_policy_allows_it() {
+    // test setup
+    let mut ctx = TestContext::new().with_lock_policy(LockPolicy {
+        deposit: LockAction::Allow,
+        ..LockPolicy::default()
+    });
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.lock_test_client().await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_2,
+            amount: AmountInMinorUnits::from(5),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(105));
+}
+
+#[tokio::test]
+async fn locked_account_rejects_withdrawal_by_default() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.lock_test_client().await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Withdrawal(Withdrawal {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_2,
+            amount: AmountInMinorUnits::from(10),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(100));
+}
+
+#[tokio::test]
+async fn locked_account_accepts_withdrawal_when_policy_allows_it() {
+    // test setup
+    let mut ctx = TestContext::new().with_lock_policy(LockPolicy {
+        withdrawal: LockAction::Allow,
+        ..LockPolicy::default()
+    });
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.lock_test_client().await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Withdrawal(Withdrawal {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_2,
+            amount: AmountInMinorUnits::from(10),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(90));
+}
+
+#[tokio::test]
+async fn locked_account_rejects_dispute_by_default() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+    ctx.lock_test_client().await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(0));
+}
+
+#[tokio::test]
+async fn locked_account_accepts_dispute_when_policy_allows_it() {
+    // test setup
+    let mut ctx = TestContext::new().with_lock_policy(LockPolicy {
+        dispute: LockAction::Allow,
+        ..LockPolicy::default()
+    });
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+    ctx.lock_test_client().await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(100));
+}
+
+#[tokio::test]
+async fn locked_account_accepts_resolve_by_default() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_amount(AmountInMinorUnits::from(100), AmountInMinorUnits::from(50))
+        .await;
+    ctx.lock_test_client().await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(0));
+}
+
+#[tokio::test]
+async fn locked_account_rejects_resolve_when_policy_forbids_it() {
+    // test setup
+    let mut ctx = TestContext::new().with_lock_policy(LockPolicy {
+        resolve: LockAction::Reject,
+        ..LockPolicy::default()
+    });
+    ctx.with_disputed_amount(AmountInMinorUnits::from(100), AmountInMinorUnits::from(50))
+        .await;
+    ctx.lock_test_client().await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(50));
+}
+
+#[tokio::test]
+async fn locked_account_accepts_chargeback_by_default() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_amount(AmountInMinorUnits::from(100), AmountInMinorUnits::from(50))
+        .await;
+    ctx.lock_test_client().await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(100));
+}
+
+#[tokio::test]
+async fn locked_account_rejects_chargeback_when_policy_forbids_it() {
+    // test setup
+    let mut ctx = TestContext::new().with_lock_policy(LockPolicy {
+        chargeback: LockAction::Reject,
+        ..LockPolicy::default()
+    });
+    ctx.with_disputed_amount(AmountInMinorUnits::from(100), AmountInMinorUnits::from(50))
+        .await;
+    ctx.lock_test_client().await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(150));
+}
+
+#[tokio::test]
+async fn reject_policy_does_not_affect_unlocked_accounts() {
+    // test setup
+    let mut ctx = TestContext::new().with_lock_policy(LockPolicy::allowing(vec![]).unwrap());
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_2,
+            amount: AmountInMinorUnits::from(5),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(105));
+}
+
+#[tokio::test]
+async fn locked_out_transaction_is_reported_as_rejected() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.lock_test_client().await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Withdrawal(Withdrawal {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_2,
+            amount: AmountInMinorUnits::from(10),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::AccountLocked
+        }
+    );
+}
@@ -0,0 +1,146 @@
+use crate::adapters::file::read_overdraft_limits;
+use crate::domain::engine::tests::test_helpers::{
+    test_client, withdraw, TestContext, OTHER_CLIENT_ID, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
+    TEST_TRANSACTION_ID_2,
+};
+use crate::domain::model::{
+    AmountInMinorUnits, Client, OverdraftPolicy, RejectionReason, TransactionOutcome,
+};
+use crate::domain::ports::{ClientRepository, Engine};
+use std::collections::HashMap;
+use std::fs;
+
+fn insufficient_funds() -> TransactionOutcome {
+    TransactionOutcome::Rejected {
+        reason: RejectionReason::InsufficientFunds,
+    }
+}
+
+#[tokio::test]
+async fn withdrawal_of_exact_available_balance_is_applied() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(withdraw(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, 100))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(0));
+}
+
+#[tokio::test]
+async fn withdrawal_within_fixed_overdraft_limit_is_applied() {
+    // test setup
+    let mut ctx = TestContext::new()
+        .with_overdraft_policy(OverdraftPolicy::Fixed(AmountInMinorUnits::from(50)));
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(withdraw(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, 150))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = ctx.get_clients().await;
+    assert_eq!(
+        clients[0].available,
+        AmountInMinorUnits::from(0) - AmountInMinorUnits::from(50)
+    );
+}
+
+#[tokio::test]
+async fn withdrawal_beyond_fixed_overdraft_limit_is_rejected() {
+    // test setup
+    let mut ctx = TestContext::new()
+        .with_overdraft_policy(OverdraftPolicy::Fixed(AmountInMinorUnits::from(50)));
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(withdraw(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, 151))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, insufficient_funds());
+}
+
+#[tokio::test]
+async fn overdraft_limit_table_only_applies_to_listed_clients() {
+    // test setup
+    let mut limits = HashMap::new();
+    limits.insert(OTHER_CLIENT_ID, AmountInMinorUnits::from(50));
+    let mut ctx = TestContext::new().with_overdraft_policy(OverdraftPolicy::Table(limits));
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.client_repo
+        .insert(Client {
+            id: OTHER_CLIENT_ID,
+            ..test_client(AmountInMinorUnits::from(100))
+        })
+        .await
+        .unwrap();
+
+    // test subject
+    let unlisted_outcome = ctx
+        .engine
+        .process_transaction(withdraw(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, 120))
+        .await
+        .unwrap();
+    let listed_outcome = ctx
+        .engine
+        .process_transaction(withdraw(OTHER_CLIENT_ID, TEST_TRANSACTION_ID_2, 120))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(unlisted_outcome, insufficient_funds());
+    assert_eq!(listed_outcome, TransactionOutcome::Applied);
+}
+
+#[tokio::test]
+async fn overdraft_limits_are_read_from_csv_file() {
+    // test setup
+    let path = std::env::temp_dir().join(format!(
+        "payments-engine-overdraft-{}.csv",
+        std::process::id()
+    ));
+    fs::write(&path, "client, limit\n1, 25.5\n2, 100\n").unwrap();
+
+    // test subject
+    let policy = read_overdraft_limits(&path);
+
+    // check results
+    fs::remove_file(&path).unwrap();
+    let policy = policy.unwrap();
+    assert_eq!(
+        policy.limit_for(&TEST_CLIENT_ID),
+        "25.5".parse::<AmountInMinorUnits>().unwrap()
+    );
+    assert_eq!(
+        policy.limit_for(&OTHER_CLIENT_ID),
+        AmountInMinorUnits::from(100)
+    );
+}
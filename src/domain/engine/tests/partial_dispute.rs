@@ -0,0 +1,323 @@
+use crate::domain::engine::tests::test_helpers::{
+    dispute, TestContext, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
+};
+use crate::domain::model::{
+    AmountInMinorUnits, Chargeback, RejectionReason, Resolve, Transaction, TransactionOutcome,
+    TransactionStatus,
+};
+use crate::domain::ports::{Engine, TransactionsRepository};
+
+#[tokio::test]
+async fn partial_dispute_holds_only_the_disputed_amount() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(30)))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(70));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(30));
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    assert_eq!(stored.disputed, AmountInMinorUnits::from(30));
+}
+
+#[tokio::test]
+async fn partial_disputes_are_accepted_up_to_the_original_amount() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(30)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(50)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(30)))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::DisputedAmountExceeded
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(20));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(80));
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    // adding to an open dispute doesn't count as another dispute cycle
+    assert_eq!(stored.disputes, 1);
+}
+
+#[tokio::test]
+async fn dispute_without_amount_covers_the_undisputed_rest() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(30)))
+        .await
+        .unwrap();
+
+    // test subject
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, None))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(0));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(100));
+}
+
+#[tokio::test]
+async fn partial_chargeback_reverses_part_of_the_dispute() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(60)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: Some(AmountInMinorUnits::from(20)),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(40));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(40));
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(80));
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    assert_eq!(stored.status, TransactionStatus::Disputed);
+    assert_eq!(stored.disputed, AmountInMinorUnits::from(40));
+    assert_eq!(stored.charged_back, AmountInMinorUnits::from(20));
+}
+
+#[tokio::test]
+async fn resolve_without_amount_releases_the_whole_open_dispute() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(30)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(20)))
+        .await
+        .unwrap();
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(100));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(0));
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    assert_eq!(stored.status, TransactionStatus::Resolved);
+}
+
+#[tokio::test]
+async fn resolve_of_more_than_the_disputed_amount_is_rejected() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(30)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: Some(AmountInMinorUnits::from(40)),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::DisputedAmountExceeded
+        }
+    );
+}
+
+#[tokio::test]
+async fn funds_never_disputed_can_be_disputed_after_a_resolve() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(10), AmountInMinorUnits::from(0))
+        .await;
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(4)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(3)))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(7));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(3));
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    assert_eq!(stored.status, TransactionStatus::Disputed);
+    assert_eq!(stored.disputes, 1);
+    assert_eq!(stored.resolved, AmountInMinorUnits::from(4));
+}
+
+#[tokio::test]
+async fn disputing_resolved_funds_again_starts_a_new_dispute_cycle() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(10), AmountInMinorUnits::from(0))
+        .await;
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(4)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(7)))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::DisputeLimitReached
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(0));
+}
+
+#[tokio::test]
+async fn dispute_without_amount_after_a_resolve_covers_the_funds_never_disputed() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(10), AmountInMinorUnits::from(0))
+        .await;
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, Some(4)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TEST_TRANSACTION_ID_1, None))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(4));
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(6));
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    assert_eq!(stored.disputes, 1);
+    assert_eq!(stored.resolved, AmountInMinorUnits::from(4));
+}
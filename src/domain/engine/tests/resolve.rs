@@ -1,7 +1,10 @@
 use crate::domain::engine::tests::test_helpers::{
-    TestContext, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
+    test_deposit, TestContext, OTHER_CLIENT_ID, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
+};
+use crate::domain::model::{
+    AmountInMinorUnits, Client, RejectionReason, Resolve, Transaction, TransactionOutcome,
+    TransactionStatus,
 };
-use crate::domain::model::{AmountInMinorUnits, Client, Resolve, Transaction, TransactionStatus};
 use crate::domain::ports::{ClientRepository, Engine, TransactionsRepository};
 
 #[tokio::test]
@@ -18,6 +21,7 @@ async fn resolve_increases_available_funds_by_disputed_amount() {
         .process_transaction(Transaction::Resolve(Resolve {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -44,6 +48,7 @@ async fn resolve_decreases_held_funds_by_disputed_amount() {
         .process_transaction(Transaction::Resolve(Resolve {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -65,16 +70,16 @@ async fn resolve_does_not_affect_available_funds_when_transaction_not_disputed()
             held: AmountInMinorUnits::from(0),
             total: starting_available_amount.clone(),
             locked: false,
+            closed: false,
         })
         .await
         .unwrap();
 
     ctx.transaction_repo
-        .store_transaction_status(TEST_TRANSACTION_ID_1, TransactionStatus::Processed)
-        .await
-        .unwrap();
-    ctx.transaction_repo
-        .store_transaction_value(TEST_TRANSACTION_ID_1, starting_available_amount.clone())
+        .insert(test_deposit(
+            starting_available_amount.clone(),
+            TransactionStatus::Processed,
+        ))
         .await
         .unwrap();
 
@@ -83,6 +88,7 @@ async fn resolve_does_not_affect_available_funds_when_transaction_not_disputed()
         .process_transaction(Transaction::Resolve(Resolve {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -104,16 +110,16 @@ async fn resolve_does_not_affect_held_funds_when_transaction_not_disputed() {
             held: AmountInMinorUnits::from(0),
             total: starting_available_amount.clone(),
             locked: false,
+            closed: false,
         })
         .await
         .unwrap();
 
     ctx.transaction_repo
-        .store_transaction_status(TEST_TRANSACTION_ID_1, TransactionStatus::Processed)
-        .await
-        .unwrap();
-    ctx.transaction_repo
-        .store_transaction_value(TEST_TRANSACTION_ID_1, starting_available_amount.clone())
+        .insert(test_deposit(
+            starting_available_amount.clone(),
+            TransactionStatus::Processed,
+        ))
         .await
         .unwrap();
 
@@ -122,6 +128,7 @@ async fn resolve_does_not_affect_held_funds_when_transaction_not_disputed() {
         .process_transaction(Transaction::Resolve(Resolve {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -143,16 +150,16 @@ async fn resolve_does_not_increase_available_funds_when_dispute_already_resolved
             held: AmountInMinorUnits::from(0),
             total: starting_available_amount.clone(),
             locked: false,
+            closed: false,
         })
         .await
         .unwrap();
 
     ctx.transaction_repo
-        .store_transaction_status(TEST_TRANSACTION_ID_1, TransactionStatus::Resolved)
-        .await
-        .unwrap();
-    ctx.transaction_repo
-        .store_transaction_value(TEST_TRANSACTION_ID_1, starting_available_amount.clone())
+        .insert(test_deposit(
+            starting_available_amount.clone(),
+            TransactionStatus::Resolved,
+        ))
         .await
         .unwrap();
 
@@ -161,6 +168,7 @@ async fn resolve_does_not_increase_available_funds_when_dispute_already_resolved
         .process_transaction(Transaction::Resolve(Resolve {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -182,16 +190,16 @@ async fn resolve_does_not_decrease_held_funds_when_dispute_already_resolved() {
             held: AmountInMinorUnits::from(500),
             total: starting_available_amount.clone() + AmountInMinorUnits::from(500),
             locked: false,
+            closed: false,
         })
         .await
         .unwrap();
 
     ctx.transaction_repo
-        .store_transaction_status(TEST_TRANSACTION_ID_1, TransactionStatus::Resolved)
-        .await
-        .unwrap();
-    ctx.transaction_repo
-        .store_transaction_value(TEST_TRANSACTION_ID_1, starting_available_amount.clone())
+        .insert(test_deposit(
+            starting_available_amount.clone(),
+            TransactionStatus::Resolved,
+        ))
         .await
         .unwrap();
 
@@ -200,6 +208,7 @@ async fn resolve_does_not_decrease_held_funds_when_dispute_already_resolved() {
         .process_transaction(Transaction::Resolve(Resolve {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -221,16 +230,16 @@ async fn resolve_does_not_increase_available_funds_when_already_charged_back() {
             held: AmountInMinorUnits::from(0),
             total: AmountInMinorUnits::from(0),
             locked: false,
+            closed: false,
         })
         .await
         .unwrap();
 
     ctx.transaction_repo
-        .store_transaction_status(TEST_TRANSACTION_ID_1, TransactionStatus::ChargedBack)
-        .await
-        .unwrap();
-    ctx.transaction_repo
-        .store_transaction_value(TEST_TRANSACTION_ID_1, disputed_amount)
+        .insert(test_deposit(
+            disputed_amount,
+            TransactionStatus::ChargedBack,
+        ))
         .await
         .unwrap();
 
@@ -239,6 +248,7 @@ async fn resolve_does_not_increase_available_funds_when_already_charged_back() {
         .process_transaction(Transaction::Resolve(Resolve {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -261,16 +271,16 @@ async fn resolve_does_not_decrease_held_funds_when_already_charged_back() {
             held: held_amount.clone(),
             total: AmountInMinorUnits::from(200),
             locked: false,
+            closed: false,
         })
         .await
         .unwrap();
 
     ctx.transaction_repo
-        .store_transaction_status(TEST_TRANSACTION_ID_1, TransactionStatus::ChargedBack)
-        .await
-        .unwrap();
-    ctx.transaction_repo
-        .store_transaction_value(TEST_TRANSACTION_ID_1, chargeback_amount)
+        .insert(test_deposit(
+            chargeback_amount,
+            TransactionStatus::ChargedBack,
+        ))
         .await
         .unwrap();
 
@@ -279,6 +289,7 @@ async fn resolve_does_not_decrease_held_funds_when_already_charged_back() {
         .process_transaction(Transaction::Resolve(Resolve {
             client: TEST_CLIENT_ID,
             tx: TEST_TRANSACTION_ID_1,
+            amount: None,
         }))
         .await
         .unwrap();
@@ -287,3 +298,86 @@ async fn resolve_does_not_decrease_held_funds_when_already_charged_back() {
     let clients = ctx.get_clients().await;
     assert_eq!(clients[0].held, held_amount);
 }
+
+#[tokio::test]
+async fn resolve_is_reported_as_ignored_when_transaction_not_disputed() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(AmountInMinorUnits::from(100), AmountInMinorUnits::from(0))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Ignored {
+            reason: RejectionReason::TransactionNotDisputed
+        }
+    );
+}
+
+#[tokio::test]
+async fn resolve_is_reported_as_ignored_when_transaction_not_found() {
+    // test setup
+    let mut ctx = TestContext::new();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Ignored {
+            reason: RejectionReason::TransactionNotFound
+        }
+    );
+}
+
+#[tokio::test]
+async fn resolve_is_rejected_when_transaction_belongs_to_another_client() {
+    // test setup
+    let disputed_amount = AmountInMinorUnits::from(300);
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_amount(AmountInMinorUnits::from(100), disputed_amount.clone())
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: OTHER_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::ClientMismatch
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0].held, disputed_amount);
+}
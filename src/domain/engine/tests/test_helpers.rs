@@ -1,15 +1,33 @@
 use crate::adapters::memory::{
-    InMemoryClientRepository, InMemoryEngineDeps, InMemoryTransactionRepository,
+    InMemoryClientRepository, InMemoryEngineDeps, InMemoryJournalRepository,
+    InMemoryTransactionRepository,
 };
 use crate::domain::engine::TransactionEngine;
 use crate::domain::model::{
-    AmountInMinorUnits, Client, ClientId, TransactionId, TransactionStatus,
+    AmountInMinorUnits, AuthorizationExpiry, Authorize, Capture, Chargeback, Client, ClientId,
+    Close, Deposit, Dispute, DisputeLifecycle, DisputeWindow, DuplicatePolicy, FeeSchedule,
+    FeeTier, FeeType, Freeze, LockPolicy, OverdraftPolicy, RecordTime, Resolve, StoredTransaction,
+    Transaction, TransactionId, TransactionKind, TransactionStatus, Transfer, Unlock, Void,
+    Withdrawal,
 };
-use crate::domain::ports::{ClientRepository, Engine, TransactionsRepository};
+use crate::domain::ports::{
+    ClientRepository, ClientRepositoryErrors, ClientUpdate, Engine, EngineConfig, StatusUpdate,
+    TransactionRepositoryErrors, TransactionsRepository, UnitOfWork, UnitOfWorkErrors,
+};
+use async_trait::async_trait;
+use futures::prelude::stream::BoxStream;
 use futures::TryStreamExt;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_decimal::Decimal;
 
 pub const TEST_CLIENT_ID: ClientId = ClientId(1);
+pub const OTHER_CLIENT_ID: ClientId = ClientId(2);
 pub const TEST_TRANSACTION_ID_1: TransactionId = TransactionId(1);
+pub const TEST_TRANSACTION_ID_2: TransactionId = TransactionId(2);
+pub const TEST_TRANSACTION_ID_3: TransactionId = TransactionId(3);
+pub const TEST_OPERATOR: &str = "alice";
+pub const HOUSE_ACCOUNT: ClientId = ClientId(99);
 
 pub fn test_client(amount: AmountInMinorUnits) -> Client {
     Client {
@@ -18,32 +36,261 @@ pub fn test_client(amount: AmountInMinorUnits) -> Client {
         held: AmountInMinorUnits::from(0),
         total: amount,
         locked: false,
+        closed: false,
+    }
+}
+
+pub fn test_deposit(amount: AmountInMinorUnits, status: TransactionStatus) -> StoredTransaction {
+    StoredTransaction {
+        id: TEST_TRANSACTION_ID_1,
+        kind: TransactionKind::Deposit,
+        client: TEST_CLIENT_ID,
+        amount: amount.clone(),
+        // anything past processed has been through exactly one dispute
+        disputes: match status {
+            TransactionStatus::Processed => 0,
+            _ => 1,
+        },
+        disputed_at: match status {
+            TransactionStatus::Processed => None,
+            _ => Some(RecordTime::default()),
+        },
+        // disputes, resolves & chargebacks cover the whole amount
+        disputed: match status {
+            TransactionStatus::Disputed => amount.clone(),
+            _ => AmountInMinorUnits::default(),
+        },
+        charged_back: match status {
+            TransactionStatus::ChargedBack => amount.clone(),
+            _ => AmountInMinorUnits::default(),
+        },
+        resolved: match status {
+            TransactionStatus::Resolved => amount.clone(),
+            _ => AmountInMinorUnits::default(),
+        },
+        status,
+        sequence: 0,
+        timestamp: None,
+    }
+}
+
+pub fn test_withdrawal(amount: AmountInMinorUnits, status: TransactionStatus) -> StoredTransaction {
+    StoredTransaction {
+        kind: TransactionKind::Withdrawal,
+        ..test_deposit(amount, status)
+    }
+}
+
+pub fn deposit(client: ClientId, tx: TransactionId, amount: u64) -> Transaction {
+    Transaction::Deposit(Deposit {
+        client,
+        tx,
+        amount: AmountInMinorUnits::from(amount),
+    })
+}
+
+pub fn withdraw(client: ClientId, tx: TransactionId, amount: u64) -> Transaction {
+    Transaction::Withdrawal(Withdrawal {
+        client,
+        tx,
+        amount: AmountInMinorUnits::from(amount),
+    })
+}
+
+/// disputes `amount` of the transaction, or all of its undisputed funds when `None`
+pub fn dispute(client: ClientId, tx: TransactionId, amount: Option<u64>) -> Transaction {
+    Transaction::Dispute(Dispute {
+        client,
+        tx,
+        amount: amount.map(AmountInMinorUnits::from),
+    })
+}
+
+pub fn transfer(from: ClientId, to: ClientId, tx: TransactionId, amount: u64) -> Transaction {
+    Transaction::Transfer(Transfer {
+        from,
+        to,
+        tx,
+        amount: AmountInMinorUnits::from(amount),
+    })
+}
+
+pub fn authorize(client: ClientId, tx: TransactionId, amount: u64) -> Transaction {
+    Transaction::Authorize(Authorize {
+        client,
+        tx,
+        amount: AmountInMinorUnits::from(amount),
+    })
+}
+
+pub fn capture(client: ClientId, tx: TransactionId) -> Transaction {
+    Transaction::Capture(Capture { client, tx })
+}
+
+pub fn void(client: ClientId, tx: TransactionId) -> Transaction {
+    Transaction::Void(Void { client, tx })
+}
+
+pub fn unlock(client: ClientId, tx: TransactionId) -> Transaction {
+    Transaction::Unlock(Unlock {
+        client,
+        tx,
+        operator: TEST_OPERATOR.to_string(),
+    })
+}
+
+pub fn freeze(client: ClientId, tx: TransactionId) -> Transaction {
+    Transaction::Freeze(Freeze {
+        client,
+        tx,
+        operator: TEST_OPERATOR.to_string(),
+    })
+}
+
+pub fn close(client: ClientId, tx: TransactionId) -> Transaction {
+    Transaction::Close(Close {
+        client,
+        tx,
+        operator: TEST_OPERATOR.to_string(),
+    })
+}
+
+/// charges a flat `amount` on every transaction of `tx_type`, credited to the `HOUSE_ACCOUNT`
+pub fn flat_fee(tx_type: FeeType, amount: u64) -> FeeSchedule {
+    FeeSchedule::new(
+        HOUSE_ACCOUNT,
+        vec![FeeTier {
+            tx_type,
+            from: AmountInMinorUnits::default(),
+            flat: AmountInMinorUnits::from(amount),
+            percent: Decimal::default(),
+            min: None,
+            max: None,
+        }],
+    )
+}
+
+/// Generates a reproducible mix of every transaction type that moves funds, spread over many
+/// clients. Transfers are left out, so the records can also be split across shards.
+pub fn random_transactions(seed: u64, count: u32) -> Vec<Transaction> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut transactions = Vec::new();
+    // ids of the transactions made so far per client, referenced by disputes, captures & voids
+    let mut history: Vec<Vec<TransactionId>> = vec![Vec::new(); 50];
+    for id in 1..=count {
+        let client_index = rng.gen_range(0..history.len());
+        let client = ClientId(client_index as u16 + 1);
+        let tx = TransactionId(id);
+        let amount = rng.gen_range(1..100u64);
+        let past = &history[client_index];
+        let referenced = if past.is_empty() {
+            tx
+        } else {
+            past[rng.gen_range(0..past.len())]
+        };
+        let transaction = match rng.gen_range(0..13) {
+            0..=3 => deposit(client, tx, amount),
+            4..=5 => withdraw(client, tx, amount),
+            6 => authorize(client, tx, amount),
+            7 => capture(client, referenced),
+            8 => void(client, referenced),
+            9 => dispute(client, referenced, None),
+            10 => Transaction::Resolve(Resolve {
+                client,
+                tx: referenced,
+                amount: None,
+            }),
+            _ => Transaction::Chargeback(Chargeback {
+                client,
+                tx: referenced,
+                amount: None,
+            }),
+        };
+        if matches!(
+            transaction,
+            Transaction::Deposit(_) | Transaction::Withdrawal(_) | Transaction::Authorize(_)
+        ) {
+            history[client_index].push(tx);
+        }
+        transactions.push(transaction);
     }
+    transactions
 }
 
 pub struct TestContext {
     pub engine: TransactionEngine<InMemoryEngineDeps>,
     pub client_repo: InMemoryClientRepository,
     pub transaction_repo: InMemoryTransactionRepository,
+    pub journal_repo: InMemoryJournalRepository,
 }
 
 impl TestContext {
     pub fn new() -> Self {
         let client_repo = InMemoryClientRepository::default();
         let transaction_repo = InMemoryTransactionRepository::default();
+        let journal_repo = InMemoryJournalRepository::default();
 
-        let engine = TransactionEngine {
-            clients: client_repo.clone(),
-            transactions: transaction_repo.clone(),
-        };
+        let engine = TransactionEngine::new(
+            client_repo.clone(),
+            transaction_repo.clone(),
+            journal_repo.clone(),
+        );
 
         Self {
             engine,
             client_repo,
             transaction_repo,
+            journal_repo,
         }
     }
 
+    pub fn with_lock_policy(mut self, lock_policy: LockPolicy) -> Self {
+        self.engine.lock_policy = lock_policy;
+        self
+    }
+
+    pub fn with_overdraft_policy(mut self, overdraft_policy: OverdraftPolicy) -> Self {
+        self.engine.overdraft_policy = overdraft_policy;
+        self
+    }
+
+    pub fn with_duplicate_policy(mut self, duplicate_policy: DuplicatePolicy) -> Self {
+        self.engine.duplicate_policy = duplicate_policy;
+        self
+    }
+
+    pub fn with_dispute_lifecycle(mut self, dispute_lifecycle: DisputeLifecycle) -> Self {
+        self.engine.dispute_lifecycle = dispute_lifecycle;
+        self
+    }
+
+    pub fn with_dispute_window(mut self, dispute_window: DisputeWindow) -> Self {
+        self.engine.dispute_window = dispute_window;
+        self
+    }
+
+    pub fn with_authorization_expiry(mut self, authorization_expiry: AuthorizationExpiry) -> Self {
+        self.engine.authorization_expiry = authorization_expiry;
+        self
+    }
+
+    pub fn with_fee_schedule(mut self, fee_schedule: FeeSchedule) -> Self {
+        self.engine.fee_schedule = fee_schedule;
+        self
+    }
+
+    /// marks the test client's account as locked, as if it had been charged back previously
+    pub async fn lock_test_client(&mut self) {
+        let client = self.client_repo.get(&TEST_CLIENT_ID).await.unwrap();
+        self.client_repo
+            .insert(Client {
+                locked: true,
+                ..client
+            })
+            .await
+            .unwrap();
+    }
+
     pub async fn get_clients(&self) -> Vec<Client> {
         self.engine
             .get_clients()
@@ -62,16 +309,13 @@ impl TestContext {
                 held: held.clone(),
                 total: amount.clone() + held,
                 locked: false,
+                closed: false,
             })
             .await
             .unwrap();
 
         self.transaction_repo
-            .store_transaction_value(TEST_TRANSACTION_ID_1, amount.clone())
-            .await
-            .unwrap();
-        self.transaction_repo
-            .store_transaction_status(TEST_TRANSACTION_ID_1, TransactionStatus::Processed)
+            .insert(test_deposit(amount.clone(), TransactionStatus::Processed))
             .await
             .unwrap();
     }
@@ -90,16 +334,16 @@ impl TestContext {
                 held: disputed_amount.clone(),
                 total: available_amount + disputed_amount.clone(),
                 locked: false,
+                closed: false,
             })
             .await
             .unwrap();
 
         self.transaction_repo
-            .store_transaction_value(TEST_TRANSACTION_ID_1, disputed_amount.clone())
-            .await
-            .unwrap();
-        self.transaction_repo
-            .store_transaction_status(TEST_TRANSACTION_ID_1, TransactionStatus::Disputed)
+            .insert(test_deposit(
+                disputed_amount.clone(),
+                TransactionStatus::Disputed,
+            ))
             .await
             .unwrap();
     }
@@ -117,17 +361,188 @@ impl TestContext {
                 held: held_amount.clone(),
                 total: available_amount + held_amount.clone(),
                 locked: true,
+                closed: false,
             })
             .await
             .unwrap();
 
         self.transaction_repo
-            .store_transaction_value(TEST_TRANSACTION_ID_1, chargeback_amount.clone())
+            .insert(test_deposit(
+                chargeback_amount.clone(),
+                TransactionStatus::ChargedBack,
+            ))
+            .await
+            .unwrap();
+    }
+
+    /// sets up the test context with a pre-existing withdrawal,
+    /// `available_amount` is the client's balance after the withdrawal was made
+    pub async fn with_withdrawal(
+        &mut self,
+        available_amount: AmountInMinorUnits,
+        withdrawn_amount: AmountInMinorUnits,
+    ) {
+        self.client_repo
+            .insert(test_client(available_amount))
+            .await
+            .unwrap();
+
+        self.transaction_repo
+            .insert(test_withdrawal(
+                withdrawn_amount,
+                TransactionStatus::Processed,
+            ))
             .await
             .unwrap();
+    }
+
+    /// sets up the test context with a pre-existing withdrawal & dispute,
+    /// useful for testing resolve & chargeback of withdrawals
+    pub async fn with_disputed_withdrawal(
+        &mut self,
+        available_amount: AmountInMinorUnits,
+        disputed_amount: AmountInMinorUnits,
+    ) {
+        self.client_repo
+            .insert(Client {
+                id: TEST_CLIENT_ID,
+                available: available_amount.clone(),
+                held: disputed_amount.clone(),
+                total: available_amount + disputed_amount.clone(),
+                locked: false,
+                closed: false,
+            })
+            .await
+            .unwrap();
+
         self.transaction_repo
-            .store_transaction_status(TEST_TRANSACTION_ID_1, TransactionStatus::ChargedBack)
+            .insert(test_withdrawal(
+                disputed_amount,
+                TransactionStatus::Disputed,
+            ))
             .await
             .unwrap();
     }
 }
+
+/// Runs the engine against a client repository which fails every balance update,
+/// used to verify that nothing is persisted when a transaction is only partially applied
+pub struct FailingClientUpdateDeps;
+
+impl EngineConfig for FailingClientUpdateDeps {
+    type ClientRepository = FailingClientUpdateRepository;
+    type TransactionRepository = InMemoryTransactionRepository;
+    type JournalRepository = InMemoryJournalRepository;
+}
+
+pub struct FailingClientUpdateRepository(pub InMemoryClientRepository);
+
+#[async_trait]
+impl ClientRepository for FailingClientUpdateRepository {
+    async fn get_all(
+        &self,
+    ) -> Result<BoxStream<'static, Result<Client, ClientRepositoryErrors>>, ClientRepositoryErrors>
+    {
+        self.0.get_all().await
+    }
+
+    async fn get(&self, client_id: &ClientId) -> Result<Client, ClientRepositoryErrors> {
+        self.0.get(client_id).await
+    }
+
+    async fn insert(&mut self, client: Client) -> Result<(), ClientRepositoryErrors> {
+        self.0.insert(client).await
+    }
+
+    async fn update(
+        &mut self,
+        _id: &ClientId,
+        _update: ClientUpdate,
+    ) -> Result<(), ClientRepositoryErrors> {
+        Err(anyhow::anyhow!("client storage unavailable").into())
+    }
+}
+
+#[async_trait]
+impl UnitOfWork for FailingClientUpdateRepository {
+    async fn begin(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.begin().await
+    }
+
+    async fn prepare(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.prepare().await
+    }
+
+    async fn commit(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.commit().await
+    }
+
+    async fn rollback(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.rollback().await
+    }
+}
+
+/// Runs the engine against a transaction repository which refuses to prepare any unit of work,
+/// used to verify that the client repository isn't committed on its own
+pub struct FailingPrepareDeps;
+
+impl EngineConfig for FailingPrepareDeps {
+    type ClientRepository = InMemoryClientRepository;
+    type TransactionRepository = FailingPrepareRepository;
+    type JournalRepository = InMemoryJournalRepository;
+}
+
+pub struct FailingPrepareRepository(pub InMemoryTransactionRepository);
+
+#[async_trait]
+impl TransactionsRepository for FailingPrepareRepository {
+    async fn get(
+        &self,
+        transaction_id: &TransactionId,
+    ) -> Result<StoredTransaction, TransactionRepositoryErrors> {
+        self.0.get(transaction_id).await
+    }
+
+    async fn insert(
+        &mut self,
+        transaction: StoredTransaction,
+    ) -> Result<(), TransactionRepositoryErrors> {
+        self.0.insert(transaction).await
+    }
+
+    async fn update_status(
+        &mut self,
+        transaction_id: &TransactionId,
+        update: StatusUpdate,
+    ) -> Result<(), TransactionRepositoryErrors> {
+        self.0.update_status(transaction_id, update).await
+    }
+
+    async fn get_all(
+        &self,
+    ) -> Result<
+        BoxStream<'static, Result<StoredTransaction, TransactionRepositoryErrors>>,
+        TransactionRepositoryErrors,
+    > {
+        self.0.get_all().await
+    }
+}
+
+#[async_trait]
+impl UnitOfWork for FailingPrepareRepository {
+    async fn begin(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.begin().await
+    }
+
+    async fn prepare(&mut self) -> Result<(), UnitOfWorkErrors> {
+        Err(anyhow::anyhow!("transaction storage unavailable").into())
+    }
+
+    async fn commit(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.commit().await
+    }
+
+    async fn rollback(&mut self) -> Result<(), UnitOfWorkErrors> {
+        self.0.rollback().await
+    }
+}
@@ -0,0 +1,317 @@
+use crate::domain::engine::tests::test_helpers::{
+    dispute, test_client, transfer, TestContext, OTHER_CLIENT_ID, TEST_CLIENT_ID,
+    TEST_TRANSACTION_ID_2,
+};
+use crate::domain::model::{
+    AmountInMinorUnits, Chargeback, Client, RejectionReason, Resolve, Transaction, TransactionId,
+    TransactionKind, TransactionOutcome, Transfer,
+};
+use crate::domain::ports::{ClientRepository, Engine, TransactionsRepository};
+
+const TRANSFER_ID: TransactionId = TEST_TRANSACTION_ID_2;
+
+fn other_client(amount: AmountInMinorUnits) -> Client {
+    Client {
+        id: OTHER_CLIENT_ID,
+        ..test_client(amount)
+    }
+}
+
+/// clients ordered by id, as the repository doesn't keep any particular order
+async fn sorted_clients(ctx: &TestContext) -> Vec<Client> {
+    let mut clients = ctx.get_clients().await;
+    clients.sort_by_key(|client| client.id.0);
+    clients
+}
+
+#[tokio::test]
+async fn transfer_moves_funds_between_clients() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(transfer(TEST_CLIENT_ID, OTHER_CLIENT_ID, TRANSFER_ID, 40))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = sorted_clients(&ctx).await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(60));
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(60));
+    // the recipient's account is opened by the transfer
+    assert_eq!(clients[1].id, OTHER_CLIENT_ID);
+    assert_eq!(clients[1].available, AmountInMinorUnits::from(40));
+    assert_eq!(clients[1].total, AmountInMinorUnits::from(40));
+    let stored = ctx.transaction_repo.get(&TRANSFER_ID).await.unwrap();
+    assert_eq!(stored.client, TEST_CLIENT_ID);
+    assert_eq!(
+        stored.kind,
+        TransactionKind::Transfer {
+            to: OTHER_CLIENT_ID
+        }
+    );
+}
+
+#[tokio::test]
+async fn transfer_exceeding_available_funds_is_rejected() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(10)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(transfer(TEST_CLIENT_ID, OTHER_CLIENT_ID, TRANSFER_ID, 40))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::InsufficientFunds
+        }
+    );
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(10));
+}
+
+#[tokio::test]
+async fn transfer_overflowing_recipient_balance_leaves_sender_untouched() {
+    // test setup
+    let large_amount: AmountInMinorUnits = "900000000000000".parse().unwrap();
+    let mut ctx = TestContext::new();
+    ctx.with_deposit(large_amount.clone(), AmountInMinorUnits::from(0))
+        .await;
+    ctx.client_repo
+        .insert(other_client(large_amount.clone()))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Transfer(Transfer {
+            from: TEST_CLIENT_ID,
+            to: OTHER_CLIENT_ID,
+            tx: TRANSFER_ID,
+            amount: large_amount.clone(),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::BalanceOverflow
+        }
+    );
+    let clients = sorted_clients(&ctx).await;
+    assert_eq!(clients[0].available, large_amount);
+    assert_eq!(clients[1].available, large_amount);
+    assert!(ctx.transaction_repo.get(&TRANSFER_ID).await.is_err());
+}
+
+#[tokio::test]
+async fn transfer_to_same_client_is_rejected() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(transfer(TEST_CLIENT_ID, TEST_CLIENT_ID, TRANSFER_ID, 40))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::SelfTransfer
+        }
+    );
+}
+
+#[tokio::test]
+async fn transfer_to_locked_account_is_rejected() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.client_repo
+        .insert(Client {
+            locked: true,
+            ..other_client(AmountInMinorUnits::from(0))
+        })
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(transfer(TEST_CLIENT_ID, OTHER_CLIENT_ID, TRANSFER_ID, 40))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::AccountLocked
+        }
+    );
+    let clients = sorted_clients(&ctx).await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(100));
+}
+
+#[tokio::test]
+async fn disputed_transfer_holds_recipient_funds() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(transfer(TEST_CLIENT_ID, OTHER_CLIENT_ID, TRANSFER_ID, 40))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TRANSFER_ID, None))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = sorted_clients(&ctx).await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(60));
+    assert_eq!(clients[1].available, AmountInMinorUnits::from(0));
+    assert_eq!(clients[1].held, AmountInMinorUnits::from(40));
+    assert_eq!(clients[1].total, AmountInMinorUnits::from(40));
+}
+
+#[tokio::test]
+async fn transfer_can_only_be_disputed_by_sender() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(transfer(TEST_CLIENT_ID, OTHER_CLIENT_ID, TRANSFER_ID, 40))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(dispute(OTHER_CLIENT_ID, TRANSFER_ID, None))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::ClientMismatch
+        }
+    );
+}
+
+#[tokio::test]
+async fn resolved_transfer_releases_recipient_funds() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(transfer(TEST_CLIENT_ID, OTHER_CLIENT_ID, TRANSFER_ID, 40))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TRANSFER_ID, None))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TRANSFER_ID,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = sorted_clients(&ctx).await;
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(60));
+    assert_eq!(clients[1].available, AmountInMinorUnits::from(40));
+    assert_eq!(clients[1].held, AmountInMinorUnits::from(0));
+}
+
+#[tokio::test]
+async fn charged_back_transfer_returns_funds_to_sender() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(transfer(TEST_CLIENT_ID, OTHER_CLIENT_ID, TRANSFER_ID, 40))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(dispute(TEST_CLIENT_ID, TRANSFER_ID, None))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TRANSFER_ID,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = sorted_clients(&ctx).await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(100));
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(100));
+    assert!(!clients[0].locked);
+    assert_eq!(clients[1].held, AmountInMinorUnits::from(0));
+    assert_eq!(clients[1].total, AmountInMinorUnits::from(0));
+    assert!(clients[1].locked);
+}
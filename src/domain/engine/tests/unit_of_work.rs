@@ -0,0 +1,182 @@
+use crate::adapters::memory::{
+    InMemoryClientRepository, InMemoryEngineDeps, InMemoryJournalRepository,
+    InMemoryTransactionRepository,
+};
+use crate::domain::engine::tests::test_helpers::{
+    test_client, test_deposit, FailingClientUpdateDeps, FailingClientUpdateRepository,
+    FailingPrepareDeps, FailingPrepareRepository, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
+};
+use crate::domain::engine::TransactionEngine;
+use crate::domain::model::{
+    AmountInMinorUnits, Deposit, Dispute, Transaction, TransactionOutcome, TransactionStatus,
+};
+use crate::domain::ports::{
+    ClientRepository, ClientRepositoryErrors, Engine, EngineErrors, JournalRepository,
+    TransactionRepositoryErrors, TransactionsRepository, UnitOfWork, UnitOfWorkErrors,
+};
+use futures::TryStreamExt;
+
+fn failing_engine(
+    client_repo: &InMemoryClientRepository,
+    transaction_repo: &InMemoryTransactionRepository,
+) -> TransactionEngine<FailingClientUpdateDeps> {
+    TransactionEngine::new(
+        FailingClientUpdateRepository(client_repo.clone()),
+        transaction_repo.clone(),
+        InMemoryJournalRepository::default(),
+    )
+}
+
+#[tokio::test]
+async fn deposit_is_not_stored_when_client_update_fails() {
+    // test setup
+    let client_repo = InMemoryClientRepository::default();
+    let transaction_repo = InMemoryTransactionRepository::default();
+    let mut engine = failing_engine(&client_repo, &transaction_repo);
+
+    // test subject
+    let result = engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(5),
+        }))
+        .await;
+
+    // check results
+    assert!(result.is_err());
+    assert!(matches!(
+        transaction_repo.get(&TEST_TRANSACTION_ID_1).await,
+        Err(TransactionRepositoryErrors::TransactionNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn dispute_status_is_not_changed_when_client_update_fails() {
+    // test setup
+    let mut client_repo = InMemoryClientRepository::default();
+    let mut transaction_repo = InMemoryTransactionRepository::default();
+    client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+    transaction_repo
+        .insert(test_deposit(
+            AmountInMinorUnits::from(100),
+            TransactionStatus::Processed,
+        ))
+        .await
+        .unwrap();
+    let mut engine = failing_engine(&client_repo, &transaction_repo);
+
+    // test subject
+    let result = engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await;
+
+    // check results
+    assert!(result.is_err());
+    let stored = transaction_repo.get(&TEST_TRANSACTION_ID_1).await.unwrap();
+    assert_eq!(stored.status, TransactionStatus::Processed);
+}
+
+#[tokio::test]
+async fn transaction_can_be_retried_after_failed_attempt_is_rolled_back() {
+    // test setup
+    let client_repo = InMemoryClientRepository::default();
+    let transaction_repo = InMemoryTransactionRepository::default();
+    let deposit = Transaction::Deposit(Deposit {
+        client: TEST_CLIENT_ID,
+        tx: TEST_TRANSACTION_ID_1,
+        amount: AmountInMinorUnits::from(5),
+    });
+    let mut engine = failing_engine(&client_repo, &transaction_repo);
+    assert!(engine.process_transaction(deposit.clone()).await.is_err());
+
+    // test subject
+    let mut engine = TransactionEngine::<InMemoryEngineDeps>::new(
+        client_repo.clone(),
+        transaction_repo.clone(),
+        InMemoryJournalRepository::default(),
+    );
+    let outcome = engine.process_transaction(deposit).await.unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let client = client_repo.get(&TEST_CLIENT_ID).await.unwrap();
+    assert_eq!(client.total, AmountInMinorUnits::from(5));
+}
+
+#[tokio::test]
+async fn client_repository_is_rolled_back_when_transactions_cannot_begin() {
+    // test setup
+    let mut client_repo = InMemoryClientRepository::default();
+    let mut transaction_repo = InMemoryTransactionRepository::default();
+    let mut engine = TransactionEngine::<InMemoryEngineDeps>::new(
+        client_repo.clone(),
+        transaction_repo.clone(),
+        InMemoryJournalRepository::default(),
+    );
+    // a unit of work left open elsewhere makes the engine's begin fail
+    transaction_repo.begin().await.unwrap();
+
+    // test subject
+    let result = engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(5),
+        }))
+        .await;
+
+    // check results
+    assert!(matches!(
+        result,
+        Err(EngineErrors::UnitOfWorkError(
+            UnitOfWorkErrors::AlreadyStarted
+        ))
+    ));
+    assert!(client_repo.begin().await.is_ok());
+}
+
+#[tokio::test]
+async fn nothing_is_committed_or_journaled_when_a_repository_fails_to_prepare() {
+    // test setup
+    let client_repo = InMemoryClientRepository::default();
+    let transaction_repo = InMemoryTransactionRepository::default();
+    let journal_repo = InMemoryJournalRepository::default();
+    let mut engine = TransactionEngine::<FailingPrepareDeps>::new(
+        client_repo.clone(),
+        FailingPrepareRepository(transaction_repo.clone()),
+        journal_repo.clone(),
+    );
+
+    // test subject
+    let result = engine
+        .process_transaction(Transaction::Deposit(Deposit {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(5),
+        }))
+        .await;
+
+    // check results
+    assert!(result.is_err());
+    assert!(matches!(
+        client_repo.get(&TEST_CLIENT_ID).await,
+        Err(ClientRepositoryErrors::ClientNotFound(_))
+    ));
+    assert!(transaction_repo.get(&TEST_TRANSACTION_ID_1).await.is_err());
+    let journal: Vec<_> = journal_repo
+        .get_all()
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert!(journal.is_empty());
+}
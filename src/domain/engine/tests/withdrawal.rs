@@ -1,6 +1,8 @@
 use crate::domain::engine::tests::test_helpers::test_client;
-use crate::domain::model::Withdrawal;
-use crate::domain::ports::ClientRepository;
+use crate::domain::model::{
+    RejectionReason, TransactionKind, TransactionOutcome, TransactionStatus, Withdrawal,
+};
+use crate::domain::ports::{ClientRepository, TransactionsRepository};
 use crate::{
     domain::engine::tests::test_helpers::{TestContext, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1},
     domain::model::{AmountInMinorUnits, Transaction},
@@ -102,3 +104,116 @@ async fn when_available_funds_are_too_low_withdrawal_does_not_effect_total_funds
     let clients = ctx.get_clients().await;
     assert_eq!(clients[0].total, AmountInMinorUnits::from(100u64))
 }
+
+#[tokio::test]
+async fn when_available_funds_are_too_low_withdrawal_is_reported_as_rejected() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject - attempt to withdraw more than the available amount of funds
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Withdrawal(Withdrawal {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(110u64),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::InsufficientFunds
+        }
+    )
+}
+
+#[tokio::test]
+async fn successful_withdrawal_is_stored_as_processed_withdrawal() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.client_repo
+        .insert(test_client(AmountInMinorUnits::from(100)))
+        .await
+        .unwrap();
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Withdrawal(Withdrawal {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(10u64),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    assert_eq!(stored.status, TransactionStatus::Processed);
+    assert_eq!(stored.kind, TransactionKind::Withdrawal);
+}
+
+#[tokio::test]
+async fn withdrawal_does_not_decrease_available_funds_if_already_processed() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_withdrawal(AmountInMinorUnits::from(90), AmountInMinorUnits::from(10))
+        .await;
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Withdrawal(Withdrawal {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(10u64),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(90u64));
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::DuplicateTransaction
+        }
+    )
+}
+
+#[tokio::test]
+async fn withdrawal_from_unknown_client_is_reported_as_rejected() {
+    // test setup
+    let mut ctx = TestContext::new();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Withdrawal(Withdrawal {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: AmountInMinorUnits::from(10u64),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Rejected {
+            reason: RejectionReason::UnknownClient
+        }
+    );
+    assert!(ctx.get_clients().await.is_empty());
+}
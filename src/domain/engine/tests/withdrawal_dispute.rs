@@ -0,0 +1,367 @@
+use crate::domain::engine::tests::test_helpers::{
+    TestContext, TEST_CLIENT_ID, TEST_TRANSACTION_ID_1,
+};
+use crate::domain::model::{
+    AmountInMinorUnits, Chargeback, Dispute, RejectionReason, Resolve, Transaction,
+    TransactionOutcome, TransactionStatus,
+};
+use crate::domain::ports::{Engine, TransactionsRepository};
+
+#[tokio::test]
+async fn withdrawal_dispute_does_not_change_available_funds() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(900))
+}
+
+#[tokio::test]
+async fn withdrawal_dispute_increases_held_funds_by_withdrawn_amount() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(100))
+}
+
+#[tokio::test]
+async fn withdrawal_dispute_increases_total_funds_by_withdrawn_amount() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(1000))
+}
+
+#[tokio::test]
+async fn withdrawal_dispute_changes_transaction_status() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    assert_eq!(stored.status, TransactionStatus::Disputed)
+}
+
+#[tokio::test]
+async fn withdrawal_dispute_does_not_change_held_funds_if_already_disputed() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(100))
+}
+
+#[tokio::test]
+async fn withdrawal_resolve_decreases_held_funds_by_disputed_amount() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(0))
+}
+
+#[tokio::test]
+async fn withdrawal_resolve_decreases_total_funds_by_disputed_amount() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(900))
+}
+
+#[tokio::test]
+async fn withdrawal_resolve_does_not_change_available_funds() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Resolve(Resolve {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(900))
+}
+
+#[tokio::test]
+async fn withdrawal_chargeback_restores_available_funds() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(1000))
+}
+
+#[tokio::test]
+async fn withdrawal_chargeback_decreases_held_funds_by_disputed_amount() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(0))
+}
+
+#[tokio::test]
+async fn withdrawal_chargeback_does_not_change_total_funds() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(1000))
+}
+
+#[tokio::test]
+async fn withdrawal_chargeback_does_not_lock_client_account() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_disputed_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+
+    // test subject
+    ctx.engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let clients = ctx.get_clients().await;
+    assert!(!clients[0].locked)
+}
+
+#[tokio::test]
+async fn withdrawal_funds_left_after_a_partial_chargeback_can_be_disputed() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+    ctx.engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: Some(AmountInMinorUnits::from(30)),
+        }))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: Some(AmountInMinorUnits::from(20)),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(outcome, TransactionOutcome::Applied);
+    let clients = ctx.get_clients().await;
+    assert_eq!(clients[0].held, AmountInMinorUnits::from(20));
+    let stored = ctx
+        .transaction_repo
+        .get(&TEST_TRANSACTION_ID_1)
+        .await
+        .unwrap();
+    assert_eq!(stored.status, TransactionStatus::Disputed);
+    assert_eq!(stored.disputed, AmountInMinorUnits::from(20));
+    assert_eq!(stored.charged_back, AmountInMinorUnits::from(30));
+}
+
+#[tokio::test]
+async fn withdrawal_fully_charged_back_cannot_be_disputed() {
+    // test setup
+    let mut ctx = TestContext::new();
+    ctx.with_withdrawal(AmountInMinorUnits::from(900), AmountInMinorUnits::from(100))
+        .await;
+    ctx.engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+    ctx.engine
+        .process_transaction(Transaction::Chargeback(Chargeback {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // test subject
+    let outcome = ctx
+        .engine
+        .process_transaction(Transaction::Dispute(Dispute {
+            client: TEST_CLIENT_ID,
+            tx: TEST_TRANSACTION_ID_1,
+            amount: None,
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Ignored {
+            reason: RejectionReason::TransactionNotDisputable
+        }
+    );
+}
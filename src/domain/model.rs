@@ -1,61 +1,191 @@
-use rust_decimal::Decimal;
-use serde::{Deserialize, Serialize};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::fmt;
 use std::ops::{Add, Sub};
 use std::str::FromStr;
+use thiserror::Error;
 
 // owned primitives
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct ClientId(pub(crate) u16);
 
 impl FromStr for ClientId {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(ClientId(s.parse().map_err(|_| ())?))
+        s.parse()
+            .map(ClientId)
+            .map_err(|_| ParseError::InvalidClientId(s.to_string()))
     }
 }
 
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[derive(
+    Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
 pub struct TransactionId(pub(crate) u32);
 
 impl FromStr for TransactionId {
-    type Err = ();
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse()
+            .map(TransactionId)
+            .map_err(|_| ParseError::InvalidTxId(s.to_string()))
+    }
+}
+
+/// Seconds since the unix epoch at which a transaction record was created
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct Timestamp(pub(crate) u64);
+
+impl FromStr for Timestamp {
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(TransactionId(s.parse().map_err(|_| ())?))
+        s.parse()
+            .map(Timestamp)
+            .map_err(|_| ParseError::InvalidTimestamp(s.to_string()))
     }
 }
 
-#[derive(Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
-pub struct AmountInMinorUnits(Decimal);
+/// An amount of money counted in 1/10000ths of a currency unit, the precision the engine works at
+#[derive(Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub struct AmountInMinorUnits(i64);
+
+const MINOR_UNITS_PER_UNIT: i64 = 10_000;
+const DECIMAL_PLACES: u32 = 4;
 
 impl AmountInMinorUnits {
-    fn round_to_4_decimals(self) -> Self {
-        AmountInMinorUnits(self.0.round_dp(4))
+    /// Parses a transaction amount, which must be positive.
+    /// Amounts with more than four decimal places are rejected unless validation is lenient,
+    /// in which case they're rounded instead.
+    pub fn parse(s: &str, validation: AmountValidation) -> Result<Self, ParseError> {
+        let decimal: Decimal = s
+            .parse()
+            .map_err(|_| ParseError::InvalidAmount(s.to_string()))?;
+        if decimal.is_sign_negative() || decimal.is_zero() {
+            return Err(ParseError::NonPositiveAmount(s.to_string()));
+        }
+        // trailing zeros don't add any precision
+        if validation == AmountValidation::Strict && decimal.normalize().scale() > DECIMAL_PLACES {
+            return Err(ParseError::ExcessPrecision(s.to_string()));
+        }
+        let amount = AmountInMinorUnits::from_decimal(decimal)
+            .ok_or_else(|| ParseError::InvalidAmount(s.to_string()))?;
+        // lenient rounding can bring a tiny amount down to nothing
+        if amount.0 <= 0 {
+            return Err(ParseError::NonPositiveAmount(s.to_string()));
+        }
+        Ok(amount)
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(AmountInMinorUnits)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(AmountInMinorUnits)
+    }
+
+    /// `percent` percent of the amount, rounded half away from zero, `None` if it doesn't fit
+    pub fn percentage(&self, percent: Decimal) -> Option<Self> {
+        Decimal::from(self.0)
+            .checked_mul(percent)?
+            .checked_div(Decimal::from(100))?
+            .round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)
+            .to_i64()
+            .map(AmountInMinorUnits)
+    }
+
+    /// rounds to four decimal places, `None` if the amount doesn't fit
+    fn from_decimal(decimal: Decimal) -> Option<Self> {
+        decimal
+            .round_dp(DECIMAL_PLACES)
+            .checked_mul(Decimal::from(MINOR_UNITS_PER_UNIT))?
+            .to_i64()
+            .map(AmountInMinorUnits)
     }
 }
 
 impl From<u64> for AmountInMinorUnits {
     fn from(amount: u64) -> Self {
-        AmountInMinorUnits(Decimal::from(amount))
+        i64::try_from(amount)
+            .ok()
+            .and_then(|amount| amount.checked_mul(MINOR_UNITS_PER_UNIT))
+            .map(AmountInMinorUnits)
+            .expect("amount out of range")
     }
 }
 
 impl FromStr for AmountInMinorUnits {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let decimal: Decimal = s.parse().map_err(|_| ())?;
-        Ok(AmountInMinorUnits(decimal).round_to_4_decimals())
+        AmountInMinorUnits::parse(s, AmountValidation::Strict)
     }
 }
 
+/// Always formatted with four decimal places, e.g. `1.5000`
+impl fmt::Display for AmountInMinorUnits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let minor_units = self.0.unsigned_abs();
+        let per_unit = MINOR_UNITS_PER_UNIT as u64;
+        write!(
+            f,
+            "{}{}.{:04}",
+            sign,
+            minor_units / per_unit,
+            minor_units % per_unit
+        )
+    }
+}
+
+// human readable formats like CSV get the decimal string, binary formats the raw minor units
+impl Serialize for AmountInMinorUnits {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_i64(self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AmountInMinorUnits {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            // balances may be zero or negative, unlike transaction amounts
+            s.parse::<Decimal>()
+                .ok()
+                .filter(|decimal| decimal.normalize().scale() <= DECIMAL_PLACES)
+                .and_then(AmountInMinorUnits::from_decimal)
+                .ok_or_else(|| de::Error::custom(ParseError::InvalidAmount(s)))
+        } else {
+            i64::deserialize(deserializer).map(AmountInMinorUnits)
+        }
+    }
+}
+
+/// How amounts with more precision than the engine keeps are handled
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AmountValidation {
+    /// reject amounts with more than four decimal places
+    Strict,
+    /// round amounts to four decimal places
+    Lenient,
+}
+
+// panics on overflow, balances must only be changed through the checked operations
 impl Add for AmountInMinorUnits {
     type Output = AmountInMinorUnits;
 
     fn add(self, rhs: Self) -> Self::Output {
-        AmountInMinorUnits(self.0.add(rhs.0)).round_to_4_decimals()
+        self.checked_add(rhs).expect("amount overflow")
     }
 }
 
@@ -63,7 +193,7 @@ impl Sub for AmountInMinorUnits {
     type Output = AmountInMinorUnits;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        AmountInMinorUnits(self.0.sub(rhs.0)).round_to_4_decimals()
+        self.checked_sub(rhs).expect("amount overflow")
     }
 }
 
@@ -76,8 +206,14 @@ pub struct Client {
     pub(crate) held: AmountInMinorUnits,
     pub(crate) total: AmountInMinorUnits,
     pub(crate) locked: bool,
+    /// closed accounts were paid out and don't accept any further transactions.
+    /// Not part of the CSV output, snapshots keep it next to the client instead.
+    #[serde(skip)]
+    pub(crate) closed: bool,
 }
 
+/// Transactions are journaled with bincode, which encodes variants by position,
+/// so new variants are only ever added at the end
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Transaction {
     Deposit(Deposit),
@@ -85,6 +221,112 @@ pub enum Transaction {
     Dispute(Dispute),
     Resolve(Resolve),
     Chargeback(Chargeback),
+    // account operations performed by the operations team
+    Unlock(Unlock),
+    Freeze(Freeze),
+    Close(Close),
+    Transfer(Transfer),
+    // two-phase withdrawals, funds are reserved first and settled or released later on
+    Authorize(Authorize),
+    Capture(Capture),
+    Void(Void),
+}
+
+impl Transaction {
+    /// The client named on the transaction record
+    pub fn client(&self) -> ClientId {
+        match self {
+            Transaction::Deposit(deposit) => deposit.client,
+            Transaction::Withdrawal(withdrawal) => withdrawal.client,
+            Transaction::Transfer(transfer) => transfer.from,
+            Transaction::Authorize(authorize) => authorize.client,
+            Transaction::Capture(capture) => capture.client,
+            Transaction::Void(void) => void.client,
+            Transaction::Dispute(dispute) => dispute.client,
+            Transaction::Resolve(resolve) => resolve.client,
+            Transaction::Chargeback(chargeback) => chargeback.client,
+            Transaction::Unlock(unlock) => unlock.client,
+            Transaction::Freeze(freeze) => freeze.client,
+            Transaction::Close(close) => close.client,
+        }
+    }
+
+    /// The transaction id named on the transaction record
+    pub fn tx(&self) -> TransactionId {
+        match self {
+            Transaction::Deposit(deposit) => deposit.tx,
+            Transaction::Withdrawal(withdrawal) => withdrawal.tx,
+            Transaction::Transfer(transfer) => transfer.tx,
+            Transaction::Authorize(authorize) => authorize.tx,
+            Transaction::Capture(capture) => capture.tx,
+            Transaction::Void(void) => void.tx,
+            Transaction::Dispute(dispute) => dispute.tx,
+            Transaction::Resolve(resolve) => resolve.tx,
+            Transaction::Chargeback(chargeback) => chargeback.tx,
+            Transaction::Unlock(unlock) => unlock.tx,
+            Transaction::Freeze(freeze) => freeze.tx,
+            Transaction::Close(close) => close.tx,
+        }
+    }
+
+    /// The second client named on records which move funds between two clients
+    pub fn counterparty(&self) -> Option<ClientId> {
+        match self {
+            Transaction::Transfer(transfer) => Some(transfer.to),
+            _ => None,
+        }
+    }
+}
+
+/// A transaction along with the time its record was created, if the input provides one
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TimedTransaction {
+    pub(crate) transaction: Transaction,
+    pub(crate) timestamp: Option<Timestamp>,
+    /// position of the record in the whole input, only set when the input is spread over several
+    /// engines, which otherwise would each count just the records they processed
+    pub(crate) sequence: Option<u64>,
+}
+
+impl From<Transaction> for TimedTransaction {
+    fn from(transaction: Transaction) -> Self {
+        TimedTransaction {
+            transaction,
+            timestamp: None,
+            sequence: None,
+        }
+    }
+}
+
+/// When a record was processed, used to tell how much time passed between two records
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RecordTime {
+    pub(crate) sequence: u64,
+    pub(crate) timestamp: Option<Timestamp>,
+}
+
+impl RecordTime {
+    /// Time passed since `earlier`, in seconds if both records have a timestamp or in number of
+    /// records processed otherwise
+    pub fn elapsed_since(&self, earlier: &RecordTime) -> u64 {
+        match (self.timestamp, earlier.timestamp) {
+            (Some(now), Some(then)) => now.0.saturating_sub(then.0),
+            _ => self.sequence.saturating_sub(earlier.sequence),
+        }
+    }
+}
+
+/// The kinds of transactions that move funds and can later be disputed
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum TransactionKind {
+    Deposit,
+    Withdrawal,
+    /// funds moved from the stored client to `to`
+    Transfer {
+        to: ClientId,
+    },
+    /// funds reserved by a card payment, which are disputed like a withdrawal once captured
+    Authorization,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -93,6 +335,188 @@ pub enum TransactionStatus {
     Disputed,
     Resolved,
     ChargedBack,
+    // authorizations stay authorized until captured, after which they're processed like a withdrawal
+    Authorized,
+    Voided,
+}
+
+/// The dispute-family records which move a stored transaction through its lifecycle
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DisputeEvent {
+    Dispute,
+    Resolve,
+    Chargeback,
+}
+
+/// Every legal transition of a stored transaction, any other combination is refused
+const DISPUTE_TRANSITIONS: [(TransactionStatus, DisputeEvent, TransactionStatus); 6] = [
+    (
+        TransactionStatus::Processed,
+        DisputeEvent::Dispute,
+        TransactionStatus::Disputed,
+    ),
+    (
+        TransactionStatus::Disputed,
+        DisputeEvent::Resolve,
+        TransactionStatus::Resolved,
+    ),
+    (
+        TransactionStatus::Disputed,
+        DisputeEvent::Chargeback,
+        TransactionStatus::ChargedBack,
+    ),
+    // a second presentment re-opens a dispute that was resolved in the client's disfavour
+    (
+        TransactionStatus::Resolved,
+        DisputeEvent::Dispute,
+        TransactionStatus::Disputed,
+    ),
+    // disputing another part of a partially disputed transaction
+    (
+        TransactionStatus::Disputed,
+        DisputeEvent::Dispute,
+        TransactionStatus::Disputed,
+    ),
+    // disputing the part of a transaction left over by a partial chargeback
+    (
+        TransactionStatus::ChargedBack,
+        DisputeEvent::Dispute,
+        TransactionStatus::Disputed,
+    ),
+];
+
+/// What a dispute-family record does to the stored transaction it references
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DisputeStep {
+    /// status the transaction moves to
+    pub(crate) status: TransactionStatus,
+    /// part of the transaction amount the record applies to
+    pub(crate) amount: AmountInMinorUnits,
+    /// part of `amount` which was disputed and resolved before
+    pub(crate) redisputed: AmountInMinorUnits,
+    /// whether the record disputes the transaction for the first time or disputes resolved funds
+    /// again, rather than disputing funds which were never disputed
+    pub(crate) starts_cycle: bool,
+}
+
+/// The dispute state machine of stored transactions, limiting how often a transaction can be disputed
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DisputeLifecycle {
+    max_disputes: u32,
+}
+
+impl DisputeLifecycle {
+    pub fn new(max_disputes: u32) -> Self {
+        DisputeLifecycle { max_disputes }
+    }
+
+    /// what `event` does to `stored` when applied to `amount`, or to everything it can apply to
+    /// if unset, or the reason it can't be applied
+    pub fn transition(
+        &self,
+        stored: &StoredTransaction,
+        event: DisputeEvent,
+        amount: Option<&AmountInMinorUnits>,
+    ) -> Result<DisputeStep, RejectionReason> {
+        let next = DISPUTE_TRANSITIONS
+            .iter()
+            .find(|(from, on, _)| *from == stored.status && *on == event)
+            .map(|(_, _, to)| to.clone());
+        let not_applicable = match event {
+            DisputeEvent::Dispute => RejectionReason::TransactionNotDisputable,
+            _ => RejectionReason::TransactionNotDisputed,
+        };
+        let next = next.ok_or_else(|| not_applicable.clone())?;
+
+        let open = match event {
+            DisputeEvent::Dispute => stored.undisputed(),
+            _ => stored.disputed.clone(),
+        };
+        if open == AmountInMinorUnits::default() {
+            return Err(not_applicable);
+        }
+        // funds which were never disputed are disputed first, only the rest is disputed again
+        let fresh = stored.undisputed() - stored.resolved.clone();
+        let amount = match amount {
+            Some(amount) if *amount > open => return Err(RejectionReason::DisputedAmountExceeded),
+            Some(amount) => amount.clone(),
+            // resolved funds can't be disputed again once the limit is reached, the rest still can
+            None if event == DisputeEvent::Dispute
+                && stored.disputes >= self.max_disputes
+                && fresh > AmountInMinorUnits::default() =>
+            {
+                fresh.clone()
+            }
+            None => open,
+        };
+        let redisputed = match event {
+            DisputeEvent::Dispute if amount > fresh => amount.clone() - fresh,
+            _ => AmountInMinorUnits::default(),
+        };
+        let starts_cycle = event == DisputeEvent::Dispute
+            && (stored.disputes == 0 || redisputed > AmountInMinorUnits::default());
+        if starts_cycle && stored.disputes >= self.max_disputes {
+            return Err(RejectionReason::DisputeLimitReached);
+        }
+        // settling part of a dispute leaves the rest of it open
+        let status = if event != DisputeEvent::Dispute && amount < stored.disputed {
+            TransactionStatus::Disputed
+        } else {
+            next
+        };
+        Ok(DisputeStep {
+            status,
+            amount,
+            redisputed,
+            starts_cycle,
+        })
+    }
+}
+
+impl Default for DisputeLifecycle {
+    /// A transaction can be disputed once, resolved funds stay resolved
+    fn default() -> Self {
+        DisputeLifecycle::new(1)
+    }
+}
+
+/// Everything the engine remembers about a processed deposit or withdrawal
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StoredTransaction {
+    pub(crate) id: TransactionId,
+    pub(crate) kind: TransactionKind,
+    pub(crate) client: ClientId,
+    pub(crate) amount: AmountInMinorUnits,
+    pub(crate) status: TransactionStatus,
+    /// position of the originating record in the processed transaction stream
+    pub(crate) sequence: u64,
+    /// time the originating record was created, if the input provided one
+    pub(crate) timestamp: Option<Timestamp>,
+    /// number of dispute cycles the transaction has entered so far
+    pub(crate) disputes: u32,
+    /// part of the amount held by open disputes
+    pub(crate) disputed: AmountInMinorUnits,
+    /// part of the amount reversed by chargebacks
+    pub(crate) charged_back: AmountInMinorUnits,
+    /// part of the amount released by resolves, disputing it again starts a new dispute cycle
+    pub(crate) resolved: AmountInMinorUnits,
+    /// when the current or latest dispute was opened
+    pub(crate) disputed_at: Option<RecordTime>,
+}
+
+impl StoredTransaction {
+    /// part of the amount which can still be disputed
+    pub fn undisputed(&self) -> AmountInMinorUnits {
+        self.amount.clone() - self.disputed.clone() - self.charged_back.clone()
+    }
+
+    /// when the originating record was processed
+    pub fn created_at(&self) -> RecordTime {
+        RecordTime {
+            sequence: self.sequence,
+            timestamp: self.timestamp,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -109,22 +533,446 @@ pub struct Withdrawal {
     pub(crate) amount: AmountInMinorUnits,
 }
 
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Transfer {
+    /// client the funds are taken from
+    pub(crate) from: ClientId,
+    /// client the funds are paid to
+    pub(crate) to: ClientId,
+    pub(crate) tx: TransactionId,
+    pub(crate) amount: AmountInMinorUnits,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Authorize {
+    pub(crate) client: ClientId,
+    pub(crate) tx: TransactionId,
+    pub(crate) amount: AmountInMinorUnits,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Capture {
+    pub(crate) client: ClientId,
+    /// id of the authorization being settled
+    pub(crate) tx: TransactionId,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Void {
+    pub(crate) client: ClientId,
+    /// id of the authorization being released
+    pub(crate) tx: TransactionId,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Dispute {
     pub(crate) client: ClientId,
     pub(crate) tx: TransactionId,
+    /// part of the referenced transaction being disputed, if unset everything which can still be
+    /// disputed, leaving out resolved funds once the transaction can't be disputed again
+    pub(crate) amount: Option<AmountInMinorUnits>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Resolve {
     pub(crate) client: ClientId,
     pub(crate) tx: TransactionId,
+    /// part of the referenced transaction being resolved, all of it if unset
+    pub(crate) amount: Option<AmountInMinorUnits>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Chargeback {
     pub(crate) client: ClientId,
     pub(crate) tx: TransactionId,
+    /// part of the referenced transaction being charged back, all of it if unset
+    pub(crate) amount: Option<AmountInMinorUnits>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Unlock {
+    pub(crate) client: ClientId,
+    pub(crate) tx: TransactionId,
+    /// who performed the operation, kept in the journal for auditing
+    pub(crate) operator: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Freeze {
+    pub(crate) client: ClientId,
+    pub(crate) tx: TransactionId,
+    /// who performed the operation, kept in the journal for auditing
+    pub(crate) operator: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Close {
+    pub(crate) client: ClientId,
+    pub(crate) tx: TransactionId,
+    /// who performed the operation, kept in the journal for auditing
+    pub(crate) operator: String,
+}
+
+// engine outcomes
+/// What happened to a single transaction record after the engine processed it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TransactionOutcome {
+    /// The transaction changed client and/or transaction state
+    Applied,
+    /// The transaction was refused by a business rule on the client's account
+    Rejected { reason: RejectionReason },
+    /// The transaction didn't apply to the current state (e.g. references an unknown
+    /// or already settled transaction) and was skipped as a no-op
+    Ignored { reason: RejectionReason },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RejectionReason {
+    AccountLocked,
+    AccountNotLocked,
+    AccountClosed,
+    FundsHeld,
+    DisputeOpen,
+    HouseAccount,
+    InsufficientFunds,
+    UnknownClient,
+    ClientMismatch,
+    SelfTransfer,
+    DuplicateTransaction,
+    TransactionNotFound,
+    TransactionNotDisputed,
+    TransactionNotDisputable,
+    DisputeLimitReached,
+    DisputeWindowExpired,
+    DisputedAmountExceeded,
+    AuthorizationNotOpen,
+    BalanceOverflow,
+}
+
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            RejectionReason::AccountLocked => "account is locked",
+            RejectionReason::AccountNotLocked => "account is not locked",
+            RejectionReason::AccountClosed => "account is closed",
+            RejectionReason::FundsHeld => "account still has funds held by open disputes",
+            RejectionReason::DisputeOpen => "account still has transactions with open disputes",
+            RejectionReason::HouseAccount => "account collects the fees and can't be closed",
+            RejectionReason::InsufficientFunds => "insufficient available funds",
+            RejectionReason::UnknownClient => "client has no account yet",
+            RejectionReason::ClientMismatch => {
+                "referenced transaction belongs to a different client"
+            }
+            RejectionReason::SelfTransfer => "transfer sender and recipient are the same client",
+            RejectionReason::DuplicateTransaction => "transaction id was already processed",
+            RejectionReason::TransactionNotFound => "referenced transaction not found",
+            RejectionReason::TransactionNotDisputed => "referenced transaction is not disputed",
+            RejectionReason::TransactionNotDisputable => {
+                "referenced transaction can't be disputed in its current state"
+            }
+            RejectionReason::DisputeLimitReached => {
+                "referenced transaction was already disputed too many times"
+            }
+            RejectionReason::DisputeWindowExpired => {
+                "referenced transaction is too old to be disputed"
+            }
+            RejectionReason::DisputedAmountExceeded => {
+                "amount exceeds the part of the referenced transaction it can apply to"
+            }
+            RejectionReason::AuthorizationNotOpen => {
+                "referenced transaction is not an open authorization"
+            }
+            RejectionReason::BalanceOverflow => "client balance would overflow",
+        };
+        f.write_str(reason)
+    }
+}
+
+// engine policies
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LockAction {
+    Allow,
+    Reject,
+}
+
+/// Decides which transaction types may still be applied once a client's account is locked
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LockPolicy {
+    pub(crate) deposit: LockAction,
+    pub(crate) withdrawal: LockAction,
+    pub(crate) transfer: LockAction,
+    pub(crate) authorize: LockAction,
+    pub(crate) capture: LockAction,
+    pub(crate) void: LockAction,
+    pub(crate) dispute: LockAction,
+    pub(crate) resolve: LockAction,
+    pub(crate) chargeback: LockAction,
+}
+
+impl LockPolicy {
+    /// Builds a policy which only allows the named transaction types on locked accounts
+    pub fn allowing<'a>(tx_types: impl IntoIterator<Item = &'a str>) -> Result<Self, ParseError> {
+        let mut policy = LockPolicy {
+            deposit: LockAction::Reject,
+            withdrawal: LockAction::Reject,
+            transfer: LockAction::Reject,
+            authorize: LockAction::Reject,
+            capture: LockAction::Reject,
+            void: LockAction::Reject,
+            dispute: LockAction::Reject,
+            resolve: LockAction::Reject,
+            chargeback: LockAction::Reject,
+        };
+        for tx_type in tx_types {
+            match tx_type {
+                "deposit" => policy.deposit = LockAction::Allow,
+                "withdrawal" => policy.withdrawal = LockAction::Allow,
+                "transfer" => policy.transfer = LockAction::Allow,
+                "authorize" => policy.authorize = LockAction::Allow,
+                "capture" => policy.capture = LockAction::Allow,
+                "void" => policy.void = LockAction::Allow,
+                "dispute" => policy.dispute = LockAction::Allow,
+                "resolve" => policy.resolve = LockAction::Allow,
+                "chargeback" => policy.chargeback = LockAction::Allow,
+                unknown => return Err(ParseError::UnknownType(unknown.to_string())),
+            }
+        }
+        Ok(policy)
+    }
+
+    pub fn action_for(&self, transaction: &Transaction) -> LockAction {
+        match transaction {
+            Transaction::Deposit(_) => self.deposit,
+            Transaction::Withdrawal(_) => self.withdrawal,
+            Transaction::Transfer(_) => self.transfer,
+            Transaction::Authorize(_) => self.authorize,
+            Transaction::Capture(_) => self.capture,
+            Transaction::Void(_) => self.void,
+            Transaction::Dispute(_) => self.dispute,
+            Transaction::Resolve(_) => self.resolve,
+            Transaction::Chargeback(_) => self.chargeback,
+            // account operations are how locked accounts are dealt with in the first place
+            Transaction::Unlock(_) | Transaction::Freeze(_) | Transaction::Close(_) => {
+                LockAction::Allow
+            }
+        }
+    }
+}
+
+impl Default for LockPolicy {
+    /// Freeze all new activity, but let disputes and authorizations that were already in-flight
+    /// run to completion
+    fn default() -> Self {
+        LockPolicy {
+            deposit: LockAction::Reject,
+            withdrawal: LockAction::Reject,
+            transfer: LockAction::Reject,
+            authorize: LockAction::Reject,
+            capture: LockAction::Allow,
+            void: LockAction::Allow,
+            dispute: LockAction::Reject,
+            resolve: LockAction::Allow,
+            chargeback: LockAction::Allow,
+        }
+    }
+}
+
+/// Decides how far a withdrawal may take a client's available funds below zero
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum OverdraftPolicy {
+    /// available funds can't go below zero
+    #[default]
+    NoOverdraft,
+    /// every client may overdraw up to the same limit
+    Fixed(AmountInMinorUnits),
+    /// each client has their own limit, clients missing from the table can't overdraw
+    Table(HashMap<ClientId, AmountInMinorUnits>),
+}
+
+impl OverdraftPolicy {
+    pub fn limit_for(&self, client: &ClientId) -> AmountInMinorUnits {
+        match self {
+            OverdraftPolicy::NoOverdraft => AmountInMinorUnits::default(),
+            OverdraftPolicy::Fixed(limit) => limit.clone(),
+            OverdraftPolicy::Table(limits) => limits.get(client).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// whether the client can withdraw `amount` without exceeding their overdraft limit
+    pub fn allows_withdrawal(&self, client: &Client, amount: &AmountInMinorUnits) -> bool {
+        match client
+            .available
+            .clone()
+            .checked_add(self.limit_for(&client.id))
+        {
+            Some(spendable) => spendable >= *amount,
+            // only positive balances with a large limit can overflow, which covers any amount
+            None => true,
+        }
+    }
+}
+
+/// Decides what happens when a deposit or withdrawal reuses a transaction id that was already processed
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum DuplicatePolicy {
+    /// every reused id is rejected
+    #[default]
+    Reject,
+    /// an exact copy of the original record is a safe retry and is ignored, any other reuse is rejected
+    Idempotent,
+}
+
+impl DuplicatePolicy {
+    /// the outcome for a record reusing the id of `stored`
+    pub fn outcome_for(
+        &self,
+        stored: &StoredTransaction,
+        kind: TransactionKind,
+        client: &ClientId,
+        amount: &AmountInMinorUnits,
+    ) -> TransactionOutcome {
+        let reason = RejectionReason::DuplicateTransaction;
+        let is_retry = stored.kind == kind && stored.client == *client && stored.amount == *amount;
+        match self {
+            DuplicatePolicy::Idempotent if is_retry => TransactionOutcome::Ignored { reason },
+            _ => TransactionOutcome::Rejected { reason },
+        }
+    }
+}
+
+/// Limits how long after a transaction it can be disputed, and how long a dispute may stay open.
+/// Both are measured in seconds when the records have timestamps, or in number of records otherwise.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DisputeWindow {
+    /// how old a transaction may be when disputed, any age is accepted if unset
+    pub(crate) max_age: Option<u64>,
+    /// how long a dispute stays open before it's resolved automatically, disputes stay open if unset
+    pub(crate) resolution_deadline: Option<u64>,
+}
+
+impl DisputeWindow {
+    pub fn new(max_age: Option<u64>, resolution_deadline: Option<u64>) -> Self {
+        DisputeWindow {
+            max_age,
+            resolution_deadline,
+        }
+    }
+
+    /// whether `stored` is still young enough to be disputed at `now`
+    pub fn allows_dispute(&self, stored: &StoredTransaction, now: &RecordTime) -> bool {
+        self.max_age
+            .is_none_or(|max_age| now.elapsed_since(&stored.created_at()) <= max_age)
+    }
+
+    /// whether the open dispute on `stored` has been left unsettled for too long at `now`
+    pub fn is_overdue(&self, stored: &StoredTransaction, now: &RecordTime) -> bool {
+        match (self.resolution_deadline, stored.disputed_at) {
+            (Some(deadline), Some(disputed_at)) => now.elapsed_since(&disputed_at) >= deadline,
+            _ => false,
+        }
+    }
+}
+
+/// Limits how long an authorization holds funds before it's voided automatically.
+/// Measured in seconds when the records have timestamps, or in number of records otherwise.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AuthorizationExpiry {
+    /// how old an authorization may get before it's voided, authorizations never expire if unset
+    pub(crate) max_age: Option<u64>,
+}
+
+impl AuthorizationExpiry {
+    pub fn new(max_age: Option<u64>) -> Self {
+        AuthorizationExpiry { max_age }
+    }
+
+    /// whether the authorization `stored` can no longer be captured at `now`
+    pub fn is_expired(&self, stored: &StoredTransaction, now: &RecordTime) -> bool {
+        self.max_age
+            .is_some_and(|max_age| now.elapsed_since(&stored.created_at()) >= max_age)
+    }
+}
+
+/// The transaction types fees are charged on, captured authorizations are charged like withdrawals
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeeType {
+    Withdrawal,
+    Chargeback,
+}
+
+/// The fee charged on transactions of one type, for amounts of at least `from`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeTier {
+    pub(crate) tx_type: FeeType,
+    /// smallest transaction amount the tier applies to
+    pub(crate) from: AmountInMinorUnits,
+    pub(crate) flat: AmountInMinorUnits,
+    /// charged on top of the flat fee
+    pub(crate) percent: Decimal,
+    pub(crate) min: Option<AmountInMinorUnits>,
+    pub(crate) max: Option<AmountInMinorUnits>,
+}
+
+impl FeeTier {
+    /// the fee for a transaction of `amount`, kept within the tier's minimum & maximum,
+    /// `None` if it doesn't fit
+    pub fn fee_for(&self, amount: &AmountInMinorUnits) -> Option<AmountInMinorUnits> {
+        let fee = self
+            .flat
+            .clone()
+            .checked_add(amount.percentage(self.percent)?)?;
+        let fee = match &self.min {
+            Some(min) if fee < *min => min.clone(),
+            _ => fee,
+        };
+        Some(match &self.max {
+            Some(max) if fee > *max => max.clone(),
+            _ => fee,
+        })
+    }
+}
+
+/// Fees charged to clients on top of their transactions, which are credited to the house account.
+/// The default schedule doesn't charge any fees.
+///
+/// Withdrawal fees have to be covered within the client's overdraft limit, along with the withdrawal
+/// or authorization they're charged on. Chargeback fees are charged regardless of the limit, as a
+/// chargeback can't be refused, so they may leave the client with an overdrawn account.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FeeSchedule {
+    pub(crate) house_account: ClientId,
+    pub(crate) tiers: Vec<FeeTier>,
+}
+
+impl FeeSchedule {
+    pub fn new(house_account: ClientId, tiers: Vec<FeeTier>) -> Self {
+        FeeSchedule {
+            house_account,
+            tiers,
+        }
+    }
+
+    /// whether fees are credited to `client`, which is never the case without any tiers
+    pub fn is_house_account(&self, client: &ClientId) -> bool {
+        !self.tiers.is_empty() && self.house_account == *client
+    }
+
+    /// the fee for a transaction of `tx_type` with `amount`, taken from the tier with the highest
+    /// `from` the amount reaches or zero if there's none, `None` if it doesn't fit
+    pub fn fee_for(
+        &self,
+        tx_type: FeeType,
+        amount: &AmountInMinorUnits,
+    ) -> Option<AmountInMinorUnits> {
+        self.tiers
+            .iter()
+            .filter(|tier| tier.tx_type == tx_type && tier.from <= *amount)
+            .max_by_key(|tier| &tier.from)
+            .map_or(Some(AmountInMinorUnits::default()), |tier| {
+                tier.fee_for(amount)
+            })
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -134,38 +982,181 @@ pub struct InputRecord {
     pub(crate) client: String,
     pub(crate) tx: String,
     pub(crate) amount: Option<String>,
+    // the timestamp column is optional, records are aged by their position in the input without it
+    #[serde(default)]
+    pub(crate) timestamp: Option<String>,
+    // only account operations need an operator, so inputs without any can leave the column out
+    #[serde(default)]
+    pub(crate) operator: Option<String>,
+    // only transfers name a second client
+    #[serde(default)]
+    pub(crate) to_client: Option<String>,
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum ParseError {
+    #[error("unknown transaction type {0:?}")]
+    UnknownType(String),
+    #[error("missing amount")]
+    MissingAmount,
+    #[error("invalid client id {0:?}")]
+    InvalidClientId(String),
+    #[error("invalid transaction id {0:?}")]
+    InvalidTxId(String),
+    #[error("invalid amount {0:?}")]
+    InvalidAmount(String),
+    #[error("amount {0:?} must be greater than zero")]
+    NonPositiveAmount(String),
+    #[error("amount {0:?} has more than four decimal places")]
+    ExcessPrecision(String),
+    #[error("invalid timestamp {0:?}, expected seconds since the unix epoch")]
+    InvalidTimestamp(String),
+    #[error("unexpected amount {0:?}, the transaction type doesn't have an amount")]
+    UnexpectedAmount(String),
+    #[error("missing operator, account operations must name who performed them")]
+    MissingOperator,
+    #[error("missing to_client, transfers must name the client receiving the funds")]
+    MissingRecipient,
 }
 
 impl TryFrom<InputRecord> for Transaction {
-    type Error = ();
+    type Error = ParseError;
 
     fn try_from(value: InputRecord) -> Result<Self, Self::Error> {
+        value.into_transaction(AmountValidation::Strict)
+    }
+}
+
+impl InputRecord {
+    /// Converts the raw record into a transaction along with its timestamp, if it has one
+    pub fn into_timed_transaction(
+        self,
+        validation: AmountValidation,
+    ) -> Result<TimedTransaction, ParseError> {
+        let timestamp = self
+            .timestamp
+            .as_deref()
+            .map(Timestamp::from_str)
+            .transpose()?;
+        Ok(TimedTransaction {
+            transaction: self.into_transaction(validation)?,
+            timestamp,
+            sequence: None,
+        })
+    }
+
+    /// Converts the raw record into a transaction, validating amounts as configured
+    pub fn into_transaction(self, validation: AmountValidation) -> Result<Transaction, ParseError> {
         // TODO: investigate using strum to convert from string to enum variant
-        let transaction = match value.tx_type.as_str() {
+        let client = ClientId::from_str(self.client.as_str())?;
+        let tx = TransactionId::from_str(self.tx.as_str())?;
+        let transaction = match self.tx_type.as_str() {
             "deposit" => Transaction::Deposit(Deposit {
-                client: ClientId::from_str(value.client.as_str()).map_err(|_| ())?,
-                tx: TransactionId::from_str(value.tx.as_str()).map_err(|_| ())?,
-                amount: AmountInMinorUnits::from_str(value.amount.ok_or(())?.as_str())?,
+                client,
+                tx,
+                amount: required_amount(self.amount, validation)?,
             }),
             "withdrawal" => Transaction::Withdrawal(Withdrawal {
-                client: ClientId::from_str(value.client.as_str()).map_err(|_| ())?,
-                tx: TransactionId::from_str(value.tx.as_str()).map_err(|_| ())?,
-                amount: AmountInMinorUnits::from_str(value.amount.ok_or(())?.as_str())?,
+                client,
+                tx,
+                amount: required_amount(self.amount, validation)?,
+            }),
+            "transfer" => Transaction::Transfer(Transfer {
+                from: client,
+                to: required_recipient(self.to_client)?,
+                tx,
+                amount: required_amount(self.amount, validation)?,
+            }),
+            "authorize" => Transaction::Authorize(Authorize {
+                client,
+                tx,
+                amount: required_amount(self.amount, validation)?,
             }),
+            "capture" => {
+                no_amount(self.amount)?;
+                Transaction::Capture(Capture { client, tx })
+            }
+            "void" => {
+                no_amount(self.amount)?;
+                Transaction::Void(Void { client, tx })
+            }
             "dispute" => Transaction::Dispute(Dispute {
-                client: ClientId::from_str(value.client.as_str()).map_err(|_| ())?,
-                tx: TransactionId::from_str(value.tx.as_str()).map_err(|_| ())?,
+                client,
+                tx,
+                amount: optional_amount(self.amount, validation)?,
             }),
             "resolve" => Transaction::Resolve(Resolve {
-                client: ClientId::from_str(value.client.as_str()).map_err(|_| ())?,
-                tx: TransactionId::from_str(value.tx.as_str()).map_err(|_| ())?,
+                client,
+                tx,
+                amount: optional_amount(self.amount, validation)?,
             }),
             "chargeback" => Transaction::Chargeback(Chargeback {
-                client: ClientId::from_str(value.client.as_str()).map_err(|_| ())?,
-                tx: TransactionId::from_str(value.tx.as_str()).map_err(|_| ())?,
+                client,
+                tx,
+                amount: optional_amount(self.amount, validation)?,
             }),
-            _ => return Err(()),
+            "unlock" => {
+                no_amount(self.amount)?;
+                Transaction::Unlock(Unlock {
+                    client,
+                    tx,
+                    operator: required_operator(self.operator)?,
+                })
+            }
+            "freeze" => {
+                no_amount(self.amount)?;
+                Transaction::Freeze(Freeze {
+                    client,
+                    tx,
+                    operator: required_operator(self.operator)?,
+                })
+            }
+            "close" => {
+                no_amount(self.amount)?;
+                Transaction::Close(Close {
+                    client,
+                    tx,
+                    operator: required_operator(self.operator)?,
+                })
+            }
+            unknown => return Err(ParseError::UnknownType(unknown.to_string())),
         };
         Ok(transaction)
     }
 }
+
+fn required_amount(
+    amount: Option<String>,
+    validation: AmountValidation,
+) -> Result<AmountInMinorUnits, ParseError> {
+    AmountInMinorUnits::parse(&amount.ok_or(ParseError::MissingAmount)?, validation)
+}
+
+fn required_recipient(to_client: Option<String>) -> Result<ClientId, ParseError> {
+    ClientId::from_str(&to_client.ok_or(ParseError::MissingRecipient)?)
+}
+
+fn no_amount(amount: Option<String>) -> Result<(), ParseError> {
+    match amount {
+        Some(amount) => Err(ParseError::UnexpectedAmount(amount)),
+        None => Ok(()),
+    }
+}
+
+fn required_operator(operator: Option<String>) -> Result<String, ParseError> {
+    operator
+        .filter(|operator| !operator.is_empty())
+        .ok_or(ParseError::MissingOperator)
+}
+
+fn optional_amount(
+    amount: Option<String>,
+    validation: AmountValidation,
+) -> Result<Option<AmountInMinorUnits>, ParseError> {
+    amount
+        .map(|amount| AmountInMinorUnits::parse(&amount, validation))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests;
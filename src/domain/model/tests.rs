@@ -0,0 +1,566 @@
+use crate::domain::model::{
+    AmountInMinorUnits, AmountValidation, Client, ClientId, Deposit, DisputeEvent,
+    DisputeLifecycle, InputRecord, ParseError, RecordTime, RejectionReason, StoredTransaction,
+    Timestamp, Transaction, TransactionId, TransactionKind, TransactionStatus, Withdrawal,
+};
+use rust_decimal::Decimal;
+use std::hint::black_box;
+use std::time::Instant;
+
+fn input_record(tx_type: &str, amount: &str) -> InputRecord {
+    InputRecord {
+        tx_type: tx_type.to_string(),
+        client: "1".to_string(),
+        tx: "1".to_string(),
+        amount: Some(amount.to_string()),
+        timestamp: None,
+        operator: None,
+        to_client: None,
+    }
+}
+
+#[test]
+fn negative_deposit_is_rejected() {
+    // test subject
+    let result = input_record("deposit", "-5.0").into_transaction(AmountValidation::Strict);
+
+    // check results
+    assert_eq!(
+        result,
+        Err(ParseError::NonPositiveAmount("-5.0".to_string()))
+    );
+}
+
+#[test]
+fn zero_withdrawal_is_rejected() {
+    // test subject
+    let result = input_record("withdrawal", "0.0").into_transaction(AmountValidation::Strict);
+
+    // check results
+    assert_eq!(
+        result,
+        Err(ParseError::NonPositiveAmount("0.0".to_string()))
+    );
+}
+
+#[test]
+fn amount_with_more_than_four_decimals_is_rejected() {
+    // test subject
+    let result = input_record("deposit", "1.00005").into_transaction(AmountValidation::Strict);
+
+    // check results
+    assert_eq!(
+        result,
+        Err(ParseError::ExcessPrecision("1.00005".to_string()))
+    );
+}
+
+#[test]
+fn amount_with_trailing_zeros_is_accepted() {
+    // test subject
+    let result = input_record("deposit", "1.500000").into_transaction(AmountValidation::Strict);
+
+    // check results
+    assert_eq!(
+        result,
+        Ok(Transaction::Deposit(Deposit {
+            client: ClientId(1),
+            tx: TransactionId(1),
+            amount: "1.5".parse().unwrap(),
+        }))
+    );
+}
+
+#[test]
+fn lenient_validation_rounds_amount_to_four_decimals() {
+    // test subject
+    let result = input_record("withdrawal", "1.00006").into_transaction(AmountValidation::Lenient);
+
+    // check results
+    assert_eq!(
+        result,
+        Ok(Transaction::Withdrawal(Withdrawal {
+            client: ClientId(1),
+            tx: TransactionId(1),
+            amount: "1.0001".parse().unwrap(),
+        }))
+    );
+}
+
+#[test]
+fn lenient_validation_still_rejects_negative_amounts() {
+    // test subject
+    let result = AmountInMinorUnits::parse("-0.00001", AmountValidation::Lenient);
+
+    // check results
+    assert_eq!(
+        result,
+        Err(ParseError::NonPositiveAmount("-0.00001".to_string()))
+    );
+}
+
+#[test]
+fn lenient_validation_rejects_amounts_rounding_to_zero() {
+    // test subject
+    let result = AmountInMinorUnits::parse("0.00004", AmountValidation::Lenient);
+
+    // check results
+    assert_eq!(
+        result,
+        Err(ParseError::NonPositiveAmount("0.00004".to_string()))
+    );
+}
+
+#[test]
+fn amount_is_displayed_with_four_decimal_places() {
+    // test setup
+    let amounts = [
+        AmountInMinorUnits(15_000),
+        AmountInMinorUnits(0),
+        AmountInMinorUnits(-5_000),
+        AmountInMinorUnits(1),
+    ];
+
+    // test subject
+    let displayed: Vec<String> = amounts.iter().map(|amount| amount.to_string()).collect();
+
+    // check results
+    assert_eq!(displayed, vec!["1.5000", "0.0000", "-0.5000", "0.0001"]);
+}
+
+#[test]
+fn amount_overflow_is_detected() {
+    // test setup
+    let max = AmountInMinorUnits(i64::MAX);
+
+    // test subject
+    let sum = max.clone().checked_add(AmountInMinorUnits(1));
+    let difference = AmountInMinorUnits(i64::MIN).checked_sub(AmountInMinorUnits(1));
+
+    // check results
+    assert_eq!(sum, None);
+    assert_eq!(difference, None);
+}
+
+#[test]
+fn amount_too_large_for_minor_units_is_invalid() {
+    // test subject
+    let result = AmountInMinorUnits::parse("1000000000000000", AmountValidation::Strict);
+
+    // check results
+    assert_eq!(
+        result,
+        Err(ParseError::InvalidAmount("1000000000000000".to_string()))
+    );
+}
+
+#[test]
+fn amount_is_serialized_as_minor_units_in_binary_formats() {
+    // test setup
+    let amount: AmountInMinorUnits = "12.3456".parse().unwrap();
+
+    // test subject
+    let bytes = bincode::serialize(&amount).unwrap();
+
+    // check results
+    assert_eq!(bytes, bincode::serialize(&123_456i64).unwrap());
+    assert_eq!(
+        bincode::deserialize::<AmountInMinorUnits>(&bytes).unwrap(),
+        amount
+    );
+}
+
+#[test]
+fn client_is_serialized_to_csv_with_four_decimal_places() {
+    // test setup
+    let client = Client {
+        id: ClientId(1),
+        available: "1.5".parse().unwrap(),
+        held: AmountInMinorUnits::default(),
+        total: "1.5".parse().unwrap(),
+        locked: false,
+        closed: false,
+    };
+    let mut writer = csv::Writer::from_writer(vec![]);
+
+    // test subject
+    writer.serialize(client).unwrap();
+
+    // check results
+    let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        output,
+        "client,available,held,total,locked\n1,1.5000,0.0000,1.5000,false\n"
+    );
+}
+
+fn stored_transaction(status: TransactionStatus, disputes: u32) -> StoredTransaction {
+    StoredTransaction {
+        id: TransactionId(1),
+        kind: TransactionKind::Deposit,
+        client: ClientId(1),
+        amount: AmountInMinorUnits::from(10),
+        sequence: 1,
+        timestamp: None,
+        disputes,
+        // disputes, resolves & chargebacks cover the whole amount
+        disputed: match status {
+            TransactionStatus::Disputed => AmountInMinorUnits::from(10),
+            _ => AmountInMinorUnits::default(),
+        },
+        charged_back: match status {
+            TransactionStatus::ChargedBack => AmountInMinorUnits::from(10),
+            _ => AmountInMinorUnits::default(),
+        },
+        resolved: match status {
+            TransactionStatus::Resolved => AmountInMinorUnits::from(10),
+            _ => AmountInMinorUnits::default(),
+        },
+        disputed_at: None,
+        status,
+    }
+}
+
+#[test]
+fn dispute_lifecycle_allows_only_legal_transitions() {
+    // test setup
+    use DisputeEvent::*;
+    use TransactionStatus::*;
+    let lifecycle = DisputeLifecycle::new(2);
+    let cases = [
+        (Processed, Dispute, Ok(Disputed)),
+        (
+            Processed,
+            Resolve,
+            Err(RejectionReason::TransactionNotDisputed),
+        ),
+        (
+            Processed,
+            Chargeback,
+            Err(RejectionReason::TransactionNotDisputed),
+        ),
+        (
+            Disputed,
+            Dispute,
+            Err(RejectionReason::TransactionNotDisputable),
+        ),
+        (Disputed, Resolve, Ok(Resolved)),
+        (Disputed, Chargeback, Ok(ChargedBack)),
+        (Resolved, Dispute, Ok(Disputed)),
+        (
+            Resolved,
+            Resolve,
+            Err(RejectionReason::TransactionNotDisputed),
+        ),
+        (
+            Resolved,
+            Chargeback,
+            Err(RejectionReason::TransactionNotDisputed),
+        ),
+        (
+            ChargedBack,
+            Dispute,
+            Err(RejectionReason::TransactionNotDisputable),
+        ),
+        (
+            ChargedBack,
+            Resolve,
+            Err(RejectionReason::TransactionNotDisputed),
+        ),
+        (
+            ChargedBack,
+            Chargeback,
+            Err(RejectionReason::TransactionNotDisputed),
+        ),
+        (
+            Authorized,
+            Dispute,
+            Err(RejectionReason::TransactionNotDisputable),
+        ),
+        (
+            Authorized,
+            Resolve,
+            Err(RejectionReason::TransactionNotDisputed),
+        ),
+        (
+            Authorized,
+            Chargeback,
+            Err(RejectionReason::TransactionNotDisputed),
+        ),
+        (
+            Voided,
+            Dispute,
+            Err(RejectionReason::TransactionNotDisputable),
+        ),
+        (
+            Voided,
+            Resolve,
+            Err(RejectionReason::TransactionNotDisputed),
+        ),
+        (
+            Voided,
+            Chargeback,
+            Err(RejectionReason::TransactionNotDisputed),
+        ),
+    ];
+
+    for (status, event, expected) in cases {
+        // test subject
+        let stored = stored_transaction(status.clone(), 1);
+        let result = lifecycle
+            .transition(&stored, event, None)
+            .map(|step| step.status);
+
+        // check results
+        assert_eq!(result, expected, "{:?} on {:?}", event, status);
+    }
+}
+
+#[test]
+fn dispute_lifecycle_refuses_disputes_past_the_limit() {
+    // test setup
+    let lifecycle = DisputeLifecycle::new(2);
+    let stored = stored_transaction(TransactionStatus::Resolved, 2);
+
+    // test subject
+    let result = lifecycle.transition(&stored, DisputeEvent::Dispute, None);
+
+    // check results
+    assert_eq!(result, Err(RejectionReason::DisputeLimitReached));
+}
+
+#[test]
+fn default_dispute_lifecycle_allows_a_single_dispute() {
+    // test setup
+    let lifecycle = DisputeLifecycle::default();
+
+    // test subject
+    let first = lifecycle.transition(
+        &stored_transaction(TransactionStatus::Processed, 0),
+        DisputeEvent::Dispute,
+        None,
+    );
+    let second = lifecycle.transition(
+        &stored_transaction(TransactionStatus::Resolved, 1),
+        DisputeEvent::Dispute,
+        None,
+    );
+
+    // check results
+    assert_eq!(
+        first.map(|step| step.status),
+        Ok(TransactionStatus::Disputed)
+    );
+    assert_eq!(second, Err(RejectionReason::DisputeLimitReached));
+}
+
+#[test]
+fn partial_dispute_applies_to_the_given_amount_only() {
+    // test setup
+    let lifecycle = DisputeLifecycle::default();
+    let stored = stored_transaction(TransactionStatus::Processed, 0);
+
+    // test subject
+    let step = lifecycle
+        .transition(
+            &stored,
+            DisputeEvent::Dispute,
+            Some(&AmountInMinorUnits::from(4)),
+        )
+        .unwrap();
+
+    // check results
+    assert_eq!(step.status, TransactionStatus::Disputed);
+    assert_eq!(step.amount, AmountInMinorUnits::from(4));
+    assert!(step.starts_cycle);
+}
+
+#[test]
+fn further_partial_dispute_adds_to_the_open_dispute() {
+    // test setup
+    let lifecycle = DisputeLifecycle::default();
+    let stored = StoredTransaction {
+        disputed: AmountInMinorUnits::from(4),
+        ..stored_transaction(TransactionStatus::Disputed, 1)
+    };
+
+    // test subject
+    let step = lifecycle
+        .transition(&stored, DisputeEvent::Dispute, None)
+        .unwrap();
+
+    // check results
+    assert_eq!(step.status, TransactionStatus::Disputed);
+    assert_eq!(step.amount, AmountInMinorUnits::from(6));
+    assert!(!step.starts_cycle);
+}
+
+#[test]
+fn dispute_of_more_than_the_undisputed_amount_is_refused() {
+    // test setup
+    let lifecycle = DisputeLifecycle::default();
+    let stored = StoredTransaction {
+        disputed: AmountInMinorUnits::from(4),
+        ..stored_transaction(TransactionStatus::Disputed, 1)
+    };
+
+    // test subject
+    let result = lifecycle.transition(
+        &stored,
+        DisputeEvent::Dispute,
+        Some(&AmountInMinorUnits::from(7)),
+    );
+
+    // check results
+    assert_eq!(result, Err(RejectionReason::DisputedAmountExceeded));
+}
+
+#[test]
+fn partial_chargeback_leaves_the_rest_of_the_dispute_open() {
+    // test setup
+    let lifecycle = DisputeLifecycle::default();
+    let stored = stored_transaction(TransactionStatus::Disputed, 1);
+
+    // test subject
+    let step = lifecycle
+        .transition(
+            &stored,
+            DisputeEvent::Chargeback,
+            Some(&AmountInMinorUnits::from(3)),
+        )
+        .unwrap();
+
+    // check results
+    assert_eq!(step.status, TransactionStatus::Disputed);
+    assert_eq!(step.amount, AmountInMinorUnits::from(3));
+}
+
+#[test]
+fn elapsed_time_uses_timestamps_when_both_records_have_one() {
+    // test setup
+    let earlier = RecordTime {
+        sequence: 1,
+        timestamp: Some(Timestamp(100)),
+    };
+    let later = RecordTime {
+        sequence: 3,
+        timestamp: Some(Timestamp(160)),
+    };
+
+    // test subject
+    let elapsed = later.elapsed_since(&earlier);
+
+    // check results
+    assert_eq!(elapsed, 60);
+}
+
+#[test]
+fn elapsed_time_falls_back_to_record_count_without_timestamps() {
+    // test setup
+    let earlier = RecordTime {
+        sequence: 1,
+        timestamp: Some(Timestamp(100)),
+    };
+    let later = RecordTime {
+        sequence: 3,
+        timestamp: None,
+    };
+
+    // test subject
+    let elapsed = later.elapsed_since(&earlier);
+
+    // check results
+    assert_eq!(elapsed, 2);
+}
+
+/// Compares the integer representation against the `Decimal` one it replaced, which rounded
+/// after every operation. Run with
+/// `cargo test --release amount_arithmetic_benchmark -- --ignored --nocapture`
+#[test]
+#[ignore]
+fn amount_arithmetic_benchmark() {
+    // test setup
+    const ITERATIONS: u32 = 10_000_000;
+    let increase: AmountInMinorUnits = "1.2345".parse().unwrap();
+    let decrease: AmountInMinorUnits = "0.0345".parse().unwrap();
+    let decimal_increase: Decimal = "1.2345".parse().unwrap();
+    let decimal_decrease: Decimal = "0.0345".parse().unwrap();
+
+    // test subject
+    let start = Instant::now();
+    let mut total = AmountInMinorUnits::default();
+    for _ in 0..ITERATIONS {
+        total = black_box(total)
+            .checked_add(increase.clone())
+            .and_then(|total| total.checked_sub(decrease.clone()))
+            .unwrap();
+    }
+    let integer_elapsed = start.elapsed();
+
+    let start = Instant::now();
+    let mut decimal_total = Decimal::default();
+    for _ in 0..ITERATIONS {
+        decimal_total = (black_box(decimal_total) + decimal_increase).round_dp(4);
+        decimal_total = (decimal_total - decimal_decrease).round_dp(4);
+    }
+    let decimal_elapsed = start.elapsed();
+
+    // check results
+    println!(
+        "{} add/sub pairs: i64 minor units {:?}, Decimal {:?}",
+        ITERATIONS, integer_elapsed, decimal_elapsed
+    );
+    assert_eq!(Some(total), AmountInMinorUnits::from_decimal(decimal_total));
+}
+
+#[test]
+fn percentage_is_rounded_half_away_from_zero() {
+    // test setup
+    let amount: AmountInMinorUnits = "0.0050".parse().unwrap();
+
+    // test subject
+    let half = amount.percentage(Decimal::from(50));
+
+    // check results
+    // 25 minor units rather than the banker's rounding default of 24
+    assert_eq!(half, Some("0.0025".parse().unwrap()));
+    assert_eq!(
+        "0.0001"
+            .parse::<AmountInMinorUnits>()
+            .unwrap()
+            .percentage(Decimal::from(50)),
+        Some("0.0001".parse().unwrap())
+    );
+}
+
+#[test]
+fn only_disputing_resolved_funds_again_starts_a_new_cycle() {
+    // test setup
+    let lifecycle = DisputeLifecycle::new(2);
+    let stored = StoredTransaction {
+        resolved: AmountInMinorUnits::from(4),
+        ..stored_transaction(TransactionStatus::Resolved, 1)
+    };
+
+    // test subject
+    let fresh = lifecycle
+        .transition(
+            &stored,
+            DisputeEvent::Dispute,
+            Some(&AmountInMinorUnits::from(6)),
+        )
+        .unwrap();
+    let again = lifecycle
+        .transition(
+            &stored,
+            DisputeEvent::Dispute,
+            Some(&AmountInMinorUnits::from(8)),
+        )
+        .unwrap();
+
+    // check results
+    assert!(!fresh.starts_cycle);
+    assert_eq!(fresh.redisputed, AmountInMinorUnits::from(0));
+    assert!(again.starts_cycle);
+    assert_eq!(again.redisputed, AmountInMinorUnits::from(2));
+}
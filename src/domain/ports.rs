@@ -1,32 +1,73 @@
 use crate::domain::model::{
-    AmountInMinorUnits, Client, ClientId, Transaction, TransactionId, TransactionStatus,
+    AmountInMinorUnits, Client, ClientId, RecordTime, StoredTransaction, TimedTransaction,
+    Transaction, TransactionId, TransactionOutcome, TransactionStatus,
 };
 use async_trait::async_trait;
 use futures::prelude::stream::BoxStream;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-pub type EngineResult = Result<(), EngineErrors>;
+/// Business rejections are reported through the outcome, errors are reserved for infrastructure failures
+pub type EngineResult = Result<TransactionOutcome, EngineErrors>;
 
 #[async_trait]
 pub trait Engine {
-    async fn process_transaction(&mut self, transaction: Transaction) -> EngineResult;
+    // used by tests, the runner always passes the timestamp read with the transaction
+    #[allow(dead_code)]
+    async fn process_transaction(&mut self, transaction: Transaction) -> EngineResult {
+        self.process_timed_transaction(transaction.into()).await
+    }
+    async fn process_timed_transaction(&mut self, record: TimedTransaction) -> EngineResult;
     async fn get_clients(
         &self,
     ) -> Result<BoxStream<'static, Result<Client, EngineErrors>>, EngineErrors>;
 }
 
 #[derive(Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum EngineErrors {
     #[error(transparent)]
     ClientError(#[from] ClientRepositoryErrors),
     #[error(transparent)]
     TransactionError(#[from] TransactionRepositoryErrors),
+    #[error(transparent)]
+    UnitOfWorkError(#[from] UnitOfWorkErrors),
+    #[error(transparent)]
+    JournalError(#[from] JournalRepositoryErrors),
 }
 
 /// Use associated types to wrap generic constraints for dependency injection
 pub trait EngineConfig {
-    type ClientRepository: ClientRepository + Send + Sync;
-    type TransactionRepository: TransactionsRepository + Send + Sync;
+    type ClientRepository: ClientRepository + UnitOfWork + Send + Sync;
+    type TransactionRepository: TransactionsRepository + UnitOfWork + Send + Sync;
+    type JournalRepository: JournalRepository + Send + Sync;
+}
+
+/// Lets a repository group all writes made while processing a single transaction,
+/// so they're either all persisted on commit or all discarded on rollback.
+/// Units of work spanning several repositories are committed in two phases: every repository is
+/// prepared first, and only once all of them succeeded are they committed.
+#[async_trait]
+pub trait UnitOfWork {
+    async fn begin(&mut self) -> Result<(), UnitOfWorkErrors>;
+
+    /// checks the staged writes can be persisted, a commit following a successful prepare must not fail
+    async fn prepare(&mut self) -> Result<(), UnitOfWorkErrors>;
+
+    async fn commit(&mut self) -> Result<(), UnitOfWorkErrors>;
+
+    async fn rollback(&mut self) -> Result<(), UnitOfWorkErrors>;
+}
+
+#[derive(Error, Debug)]
+pub enum UnitOfWorkErrors {
+    #[error("a unit of work is already in progress")]
+    AlreadyStarted,
+    #[error("no unit of work in progress")]
+    NotStarted,
+    // used to capture errors such as connectivity issues with a database
+    #[error(transparent)]
+    AdapterError(#[from] anyhow::Error),
 }
 
 #[async_trait]
@@ -46,6 +87,9 @@ pub trait ClientRepository {
     ) -> Result<(), ClientRepositoryErrors>;
 }
 
+/// Updates are journaled with bincode, which encodes variants by position,
+/// so new variants are only ever added at the end
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ClientUpdate {
     Deposit {
         available_increase: AmountInMinorUnits,
@@ -67,6 +111,50 @@ pub enum ClientUpdate {
         held_decrease: AmountInMinorUnits,
         total_decrease: AmountInMinorUnits,
     },
+    // a disputed withdrawal is provisionally credited back to the client as held funds
+    WithdrawalDispute {
+        held_increase: AmountInMinorUnits,
+        total_increase: AmountInMinorUnits,
+    },
+    // the withdrawal stands, so the provisional credit is removed again
+    WithdrawalResolve {
+        held_decrease: AmountInMinorUnits,
+        total_decrease: AmountInMinorUnits,
+    },
+    // the withdrawal is reversed and the funds are returned to the client
+    WithdrawalChargeback {
+        held_decrease: AmountInMinorUnits,
+        available_increase: AmountInMinorUnits,
+    },
+    Unlock,
+    Freeze,
+    // the remaining funds are paid out to the client and the account can't be used anymore
+    Close {
+        available_decrease: AmountInMinorUnits,
+        total_decrease: AmountInMinorUnits,
+    },
+    // funds reserved by an authorization are held until it's captured or voided
+    Authorize {
+        available_decrease: AmountInMinorUnits,
+        held_increase: AmountInMinorUnits,
+    },
+    Capture {
+        held_decrease: AmountInMinorUnits,
+        total_decrease: AmountInMinorUnits,
+    },
+    Void {
+        held_decrease: AmountInMinorUnits,
+        available_increase: AmountInMinorUnits,
+    },
+    // fees are separate ledger entries, charged to the client and credited to the house account
+    Fee {
+        available_decrease: AmountInMinorUnits,
+        total_decrease: AmountInMinorUnits,
+    },
+    FeeIncome {
+        available_increase: AmountInMinorUnits,
+        total_increase: AmountInMinorUnits,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -76,31 +164,45 @@ pub enum ClientRepositoryErrors {
     AdapterError(#[from] anyhow::Error),
     #[error("client not found with id {0:?}")]
     ClientNotFound(ClientId),
+    #[error("balance of client {0:?} would overflow")]
+    BalanceOverflow(ClientId),
 }
 
 #[async_trait]
 pub trait TransactionsRepository {
-    async fn get_transaction_status(
+    async fn get(
         &self,
         transaction_id: &TransactionId,
-    ) -> Result<TransactionStatus, TransactionRepositoryErrors>;
+    ) -> Result<StoredTransaction, TransactionRepositoryErrors>;
 
-    async fn store_transaction_status(
+    async fn insert(
         &mut self,
-        transaction_id: TransactionId,
-        transaction_status: TransactionStatus,
+        transaction: StoredTransaction,
     ) -> Result<(), TransactionRepositoryErrors>;
 
-    async fn get_transaction_value(
-        &self,
-        transaction_id: &TransactionId,
-    ) -> Result<AmountInMinorUnits, TransactionRepositoryErrors>;
-
-    async fn store_transaction_value(
+    async fn update_status(
         &mut self,
-        transaction_id: TransactionId,
-        amount: AmountInMinorUnits,
+        transaction_id: &TransactionId,
+        update: StatusUpdate,
     ) -> Result<(), TransactionRepositoryErrors>;
+
+    async fn get_all(
+        &self,
+    ) -> Result<
+        BoxStream<'static, Result<StoredTransaction, TransactionRepositoryErrors>>,
+        TransactionRepositoryErrors,
+    >;
+}
+
+/// The parts of a stored transaction which change as it moves through its dispute lifecycle
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StatusUpdate {
+    pub(crate) status: TransactionStatus,
+    pub(crate) disputed: AmountInMinorUnits,
+    pub(crate) charged_back: AmountInMinorUnits,
+    pub(crate) resolved: AmountInMinorUnits,
+    pub(crate) disputes: u32,
+    pub(crate) disputed_at: Option<RecordTime>,
 }
 
 #[derive(Error, Debug)]
@@ -111,3 +213,51 @@ pub enum TransactionRepositoryErrors {
     #[error(transparent)]
     AdapterError(#[from] anyhow::Error),
 }
+
+/// Format version of journaled entries, bump whenever the layout of `JournalEntry` or anything it
+/// contains changes
+pub const JOURNAL_VERSION: u32 = 2;
+
+/// An applied transaction along with every repository change it caused, in the order they were made
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub(crate) sequence: u64,
+    pub(crate) transaction: Transaction,
+    pub(crate) changes: Vec<JournaledChange>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum JournaledChange {
+    Client {
+        id: ClientId,
+        update: ClientUpdate,
+    },
+    TransactionInserted(StoredTransaction),
+    TransactionStatus {
+        id: TransactionId,
+        update: StatusUpdate,
+    },
+}
+
+#[async_trait]
+pub trait JournalRepository {
+    async fn append(&mut self, entry: JournalEntry) -> Result<(), JournalRepositoryErrors>;
+
+    async fn get_all(
+        &self,
+    ) -> Result<
+        BoxStream<'static, Result<JournalEntry, JournalRepositoryErrors>>,
+        JournalRepositoryErrors,
+    >;
+}
+
+#[derive(Error, Debug)]
+pub enum JournalRepositoryErrors {
+    // used to capture errors such as io failures when writing to a file
+    #[error(transparent)]
+    AdapterError(#[from] anyhow::Error),
+    #[error("journal has no version header, it was written by an unsupported build")]
+    MissingVersion,
+    #[error("unsupported journal version {0}, expected {}", JOURNAL_VERSION)]
+    UnsupportedVersion(u32),
+}
@@ -0,0 +1,112 @@
+use crate::domain::model::{ClientId, TransactionId};
+use crate::domain::ports::{
+    ClientRepository, ClientRepositoryErrors, JournalRepository, JournalRepositoryErrors,
+    JournaledChange, TransactionRepositoryErrors, TransactionsRepository,
+};
+use futures::TryStreamExt;
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ReplayErrors {
+    #[error(transparent)]
+    JournalError(#[from] JournalRepositoryErrors),
+    #[error(transparent)]
+    ClientError(#[from] ClientRepositoryErrors),
+    #[error(transparent)]
+    TransactionError(#[from] TransactionRepositoryErrors),
+    #[error("replayed state of client {0:?} doesn't match the live state")]
+    ClientMismatch(ClientId),
+    #[error("replayed state of transaction {0:?} doesn't match the live state")]
+    TransactionMismatch(TransactionId),
+}
+
+/// Rebuilds client and transaction state by re-applying every change recorded in the journal.
+/// Business rules aren't evaluated again, so the result only depends on the journal contents.
+/// Returns the sequence number of the last replayed entry.
+pub async fn replay_journal<J, C, T>(
+    journal: &J,
+    clients: &mut C,
+    transactions: &mut T,
+) -> Result<u64, ReplayErrors>
+where
+    J: JournalRepository,
+    C: ClientRepository,
+    T: TransactionsRepository,
+{
+    let mut last_sequence = 0;
+    let mut entries = journal.get_all().await?;
+    while let Some(entry) = entries.try_next().await? {
+        for change in entry.changes {
+            match change {
+                JournaledChange::Client { id, update } => clients.update(&id, update).await?,
+                JournaledChange::TransactionInserted(transaction) => {
+                    transactions.insert(transaction).await?
+                }
+                JournaledChange::TransactionStatus { id, update } => {
+                    transactions.update_status(&id, update).await?
+                }
+            }
+        }
+        last_sequence = entry.sequence;
+    }
+    Ok(last_sequence)
+}
+
+/// Checks that replayed repositories hold exactly the same clients and transactions as the live ones
+pub async fn verify_replay<C, T>(
+    live_clients: &C,
+    live_transactions: &T,
+    replayed_clients: &C,
+    replayed_transactions: &T,
+) -> Result<(), ReplayErrors>
+where
+    C: ClientRepository,
+    T: TransactionsRepository,
+{
+    let live: HashMap<_, _> = live_clients
+        .get_all()
+        .await?
+        .map_ok(|client| (client.id, client))
+        .try_collect()
+        .await?;
+    let mut replayed: HashMap<_, _> = replayed_clients
+        .get_all()
+        .await?
+        .map_ok(|client| (client.id, client))
+        .try_collect()
+        .await?;
+    for (id, client) in live {
+        if replayed.remove(&id) != Some(client) {
+            return Err(ReplayErrors::ClientMismatch(id));
+        }
+    }
+    if let Some(id) = replayed.keys().next() {
+        return Err(ReplayErrors::ClientMismatch(*id));
+    }
+
+    let live: HashMap<_, _> = live_transactions
+        .get_all()
+        .await?
+        .map_ok(|transaction| (transaction.id, transaction))
+        .try_collect()
+        .await?;
+    let mut replayed: HashMap<_, _> = replayed_transactions
+        .get_all()
+        .await?
+        .map_ok(|transaction| (transaction.id, transaction))
+        .try_collect()
+        .await?;
+    for (id, transaction) in live {
+        if replayed.remove(&id) != Some(transaction) {
+            return Err(ReplayErrors::TransactionMismatch(id));
+        }
+    }
+    if let Some(id) = replayed.keys().next() {
+        return Err(ReplayErrors::TransactionMismatch(*id));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,311 @@
+use crate::adapters::file::FileJournalRepository;
+use crate::adapters::memory::{
+    InMemoryClientRepository, InMemoryEngineDeps, InMemoryJournalRepository,
+    InMemoryTransactionRepository,
+};
+use crate::domain::engine::TransactionEngine;
+use crate::domain::model::{
+    AmountInMinorUnits, Chargeback, Client, ClientId, Deposit, Dispute, Transaction, TransactionId,
+    Withdrawal,
+};
+use crate::domain::ports::{
+    ClientRepository, Engine, JournalEntry, JournalRepository, JournalRepositoryErrors,
+    JOURNAL_VERSION,
+};
+use crate::domain::replay::{replay_journal, verify_replay, ReplayErrors};
+use futures::TryStreamExt;
+use std::fs;
+
+const CLIENT_1: ClientId = ClientId(1);
+const CLIENT_2: ClientId = ClientId(2);
+
+fn test_transactions() -> Vec<Transaction> {
+    vec![
+        Transaction::Deposit(Deposit {
+            client: CLIENT_1,
+            tx: TransactionId(1),
+            amount: AmountInMinorUnits::from(100),
+        }),
+        Transaction::Deposit(Deposit {
+            client: CLIENT_2,
+            tx: TransactionId(2),
+            amount: AmountInMinorUnits::from(50),
+        }),
+        Transaction::Withdrawal(Withdrawal {
+            client: CLIENT_1,
+            tx: TransactionId(3),
+            amount: AmountInMinorUnits::from(30),
+        }),
+        Transaction::Dispute(Dispute {
+            client: CLIENT_2,
+            tx: TransactionId(2),
+            amount: None,
+        }),
+        Transaction::Chargeback(Chargeback {
+            client: CLIENT_2,
+            tx: TransactionId(2),
+            amount: None,
+        }),
+    ]
+}
+
+/// runs the test transactions through an engine which journals to `journal`,
+/// returning the live repositories
+async fn run_engine<J>(journal: J) -> (InMemoryClientRepository, InMemoryTransactionRepository)
+where
+    J: JournalRepository + Send + Sync,
+{
+    struct Deps<J>(J);
+    impl<J: JournalRepository + Send + Sync> crate::domain::ports::EngineConfig for Deps<J> {
+        type ClientRepository = InMemoryClientRepository;
+        type TransactionRepository = InMemoryTransactionRepository;
+        type JournalRepository = J;
+    }
+
+    let clients = InMemoryClientRepository::default();
+    let transactions = InMemoryTransactionRepository::default();
+    let mut engine =
+        TransactionEngine::<Deps<J>>::new(clients.clone(), transactions.clone(), journal);
+    for transaction in test_transactions() {
+        engine.process_transaction(transaction).await.unwrap();
+    }
+    (clients, transactions)
+}
+
+#[tokio::test]
+async fn replayed_state_matches_live_state() {
+    // test setup
+    let journal = InMemoryJournalRepository::default();
+    let (live_clients, live_transactions) = run_engine(journal.clone()).await;
+
+    // test subject
+    let mut clients = InMemoryClientRepository::default();
+    let mut transactions = InMemoryTransactionRepository::default();
+    replay_journal(&journal, &mut clients, &mut transactions)
+        .await
+        .unwrap();
+
+    // check results
+    verify_replay(&live_clients, &live_transactions, &clients, &transactions)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn replay_returns_sequence_of_last_journaled_transaction() {
+    // test setup
+    let journal = InMemoryJournalRepository::default();
+    run_engine(journal.clone()).await;
+
+    // test subject
+    let sequence = replay_journal(
+        &journal,
+        &mut InMemoryClientRepository::default(),
+        &mut InMemoryTransactionRepository::default(),
+    )
+    .await
+    .unwrap();
+
+    // check results
+    assert_eq!(sequence, test_transactions().len() as u64);
+}
+
+#[tokio::test]
+async fn verification_fails_when_live_client_diverges_from_journal() {
+    // test setup
+    let journal = InMemoryJournalRepository::default();
+    let (mut live_clients, live_transactions) = run_engine(journal.clone()).await;
+    let mut clients = InMemoryClientRepository::default();
+    let mut transactions = InMemoryTransactionRepository::default();
+    replay_journal(&journal, &mut clients, &mut transactions)
+        .await
+        .unwrap();
+    live_clients
+        .insert(Client {
+            id: CLIENT_1,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // test subject
+    let result = verify_replay(&live_clients, &live_transactions, &clients, &transactions).await;
+
+    // check results
+    assert!(matches!(
+        result,
+        Err(ReplayErrors::ClientMismatch(CLIENT_1))
+    ));
+}
+
+#[tokio::test]
+async fn file_journal_can_be_replayed() {
+    // test setup
+    let path = std::env::temp_dir().join(format!(
+        "payments-engine-journal-{}.bin",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let (live_clients, live_transactions) =
+        run_engine(FileJournalRepository::open(&path).unwrap()).await;
+
+    // test subject
+    let mut clients = InMemoryClientRepository::default();
+    let mut transactions = InMemoryTransactionRepository::default();
+    let sequence = replay_journal(
+        &FileJournalRepository::open(&path).unwrap(),
+        &mut clients,
+        &mut transactions,
+    )
+    .await
+    .unwrap();
+
+    // check results
+    fs::remove_file(&path).unwrap();
+    assert_eq!(sequence, test_transactions().len() as u64);
+    verify_replay(&live_clients, &live_transactions, &clients, &transactions)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn incomplete_last_entry_is_dropped_before_appending() {
+    // test setup
+    let path = std::env::temp_dir().join(format!(
+        "payments-engine-journal-truncated-{}.bin",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    run_engine(FileJournalRepository::open(&path).unwrap()).await;
+    // as if the process stopped while writing the last entry
+    let length = fs::metadata(&path).unwrap().len();
+    fs::OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_len(length - 5)
+        .unwrap();
+    let entry = JournalEntry {
+        sequence: 6,
+        transaction: test_transactions().remove(0),
+        changes: Vec::new(),
+    };
+
+    // test subject
+    let mut journal = FileJournalRepository::open(&path).unwrap();
+    journal.append(entry.clone()).await.unwrap();
+    let entries: Result<Vec<JournalEntry>, _> =
+        journal.get_all().await.unwrap().try_collect().await;
+
+    // check results
+    fs::remove_file(&path).unwrap();
+    // the incomplete entry was dropped, the new one follows the last complete entry
+    let entries = entries.unwrap();
+    assert_eq!(entries.len(), test_transactions().len());
+    assert_eq!(entries.last(), Some(&entry));
+}
+
+#[tokio::test]
+async fn incomplete_last_entry_is_reported_when_read() {
+    // test setup
+    let path = std::env::temp_dir().join(format!(
+        "payments-engine-journal-incomplete-{}.bin",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let journal = FileJournalRepository::open(&path).unwrap();
+    run_engine(journal.clone()).await;
+    let length = fs::metadata(&path).unwrap().len();
+    fs::OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_len(length - 5)
+        .unwrap();
+
+    // test subject
+    let result = journal.get_all().await;
+
+    // check results
+    fs::remove_file(&path).unwrap();
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn journal_written_with_other_version_is_refused() {
+    // test setup
+    let path = std::env::temp_dir().join(format!(
+        "payments-engine-journal-version-{}.bin",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let journal = FileJournalRepository::open(&path).unwrap();
+    let mut header = b"PEJL".to_vec();
+    header.extend_from_slice(&(JOURNAL_VERSION + 1).to_le_bytes());
+    fs::write(&path, header).unwrap();
+
+    // test subject
+    let result = journal.get_all().await;
+
+    // check results
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        result,
+        Err(JournalRepositoryErrors::UnsupportedVersion(version)) if version == JOURNAL_VERSION + 1
+    ));
+}
+
+#[tokio::test]
+async fn journal_without_version_header_is_refused() {
+    // test setup
+    let path = std::env::temp_dir().join(format!(
+        "payments-engine-journal-unversioned-{}.bin",
+        std::process::id()
+    ));
+    // journals used to start with their first entry right away
+    let entry = JournalEntry {
+        sequence: 1,
+        transaction: test_transactions().remove(0),
+        changes: Vec::new(),
+    };
+    fs::write(&path, bincode::serialize(&entry).unwrap()).unwrap();
+
+    // test subject
+    let result = FileJournalRepository::open(&path);
+
+    // check results
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        result,
+        Err(JournalRepositoryErrors::MissingVersion)
+    ));
+}
+
+#[tokio::test]
+async fn engine_resumes_from_replayed_state() {
+    // test setup
+    let journal = InMemoryJournalRepository::default();
+    run_engine(journal.clone()).await;
+    let mut clients = InMemoryClientRepository::default();
+    let mut transactions = InMemoryTransactionRepository::default();
+    let sequence = replay_journal(&journal, &mut clients, &mut transactions)
+        .await
+        .unwrap();
+    let mut engine =
+        TransactionEngine::<InMemoryEngineDeps>::new(clients.clone(), transactions, journal)
+            .with_sequence(sequence);
+
+    // test subject
+    engine
+        .process_transaction(Transaction::Withdrawal(Withdrawal {
+            client: CLIENT_1,
+            tx: TransactionId(6),
+            amount: AmountInMinorUnits::from(20),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let client = clients.get(&CLIENT_1).await.unwrap();
+    assert_eq!(client.available, AmountInMinorUnits::from(50));
+}
@@ -0,0 +1,107 @@
+use crate::domain::model::{Client, StoredTransaction};
+use crate::domain::ports::{
+    ClientRepository, ClientRepositoryErrors, TransactionRepositoryErrors, TransactionsRepository,
+};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Format version written into every snapshot, bump whenever the layout of `Snapshot` changes
+pub const SNAPSHOT_VERSION: u32 = 8;
+
+/// Point in time copy of all client balances and stored transactions
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// sequence number of the last transaction processed before the snapshot was taken
+    pub sequence: u64,
+    pub clients: Vec<SnapshotClient>,
+    pub transactions: Vec<StoredTransaction>,
+}
+
+/// A client as stored in a snapshot, along with the state left out of its regular serialization
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotClient {
+    pub client: Client,
+    pub closed: bool,
+}
+
+impl From<Client> for SnapshotClient {
+    fn from(client: Client) -> Self {
+        SnapshotClient {
+            closed: client.closed,
+            client,
+        }
+    }
+}
+
+impl From<SnapshotClient> for Client {
+    fn from(snapshot: SnapshotClient) -> Self {
+        Client {
+            closed: snapshot.closed,
+            ..snapshot.client
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SnapshotErrors {
+    #[error(transparent)]
+    ClientError(#[from] ClientRepositoryErrors),
+    #[error(transparent)]
+    TransactionError(#[from] TransactionRepositoryErrors),
+    #[error("snapshot has no version header, it was written by an unsupported build")]
+    MissingVersion,
+    #[error("unsupported snapshot version {0}, expected {}", SNAPSHOT_VERSION)]
+    UnsupportedVersion(u32),
+}
+
+/// Copies the current contents of both repositories into a snapshot
+pub async fn take_snapshot<C, T>(
+    clients: &C,
+    transactions: &T,
+    sequence: u64,
+) -> Result<Snapshot, SnapshotErrors>
+where
+    C: ClientRepository,
+    T: TransactionsRepository,
+{
+    let mut clients: Vec<Client> = clients.get_all().await?.try_collect().await?;
+    let mut transactions: Vec<StoredTransaction> =
+        transactions.get_all().await?.try_collect().await?;
+    // keep the output stable so identical state always produces an identical file
+    clients.sort_by_key(|client| client.id.0);
+    transactions.sort_by_key(|transaction| transaction.sequence);
+    Ok(Snapshot {
+        version: SNAPSHOT_VERSION,
+        sequence,
+        clients: clients.into_iter().map(SnapshotClient::from).collect(),
+        transactions,
+    })
+}
+
+/// Loads a snapshot into the given repositories.
+/// Returns the sequence number the engine should continue from.
+pub async fn restore_snapshot<C, T>(
+    snapshot: Snapshot,
+    clients: &mut C,
+    transactions: &mut T,
+) -> Result<u64, SnapshotErrors>
+where
+    C: ClientRepository,
+    T: TransactionsRepository,
+{
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(SnapshotErrors::UnsupportedVersion(snapshot.version));
+    }
+    for client in snapshot.clients {
+        clients.insert(client.into()).await?;
+    }
+    for transaction in snapshot.transactions {
+        transactions.insert(transaction).await?;
+    }
+    Ok(snapshot.sequence)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,267 @@
+use crate::adapters::file::{read_snapshot, write_snapshot};
+use crate::adapters::memory::{
+    InMemoryClientRepository, InMemoryEngineDeps, InMemoryJournalRepository,
+    InMemoryTransactionRepository,
+};
+use crate::domain::engine::TransactionEngine;
+use crate::domain::model::{
+    AmountInMinorUnits, Client, ClientId, Deposit, Dispute, Transaction, TransactionId,
+    TransactionStatus, Withdrawal,
+};
+use crate::domain::ports::{ClientRepository, Engine, TransactionsRepository};
+use crate::domain::replay::verify_replay;
+use crate::domain::snapshot::{
+    restore_snapshot, take_snapshot, Snapshot, SnapshotErrors, SNAPSHOT_VERSION,
+};
+use std::fs;
+
+const CLIENT_1: ClientId = ClientId(1);
+const CLIENT_2: ClientId = ClientId(2);
+
+/// runs a few transactions through an in-memory engine, returning its repositories
+async fn run_engine() -> (
+    TransactionEngine<InMemoryEngineDeps>,
+    InMemoryClientRepository,
+    InMemoryTransactionRepository,
+) {
+    let clients = InMemoryClientRepository::default();
+    let transactions = InMemoryTransactionRepository::default();
+    let mut engine = TransactionEngine::<InMemoryEngineDeps>::new(
+        clients.clone(),
+        transactions.clone(),
+        InMemoryJournalRepository::default(),
+    );
+    for transaction in [
+        Transaction::Deposit(Deposit {
+            client: CLIENT_1,
+            tx: TransactionId(1),
+            amount: AmountInMinorUnits::from(100),
+        }),
+        Transaction::Deposit(Deposit {
+            client: CLIENT_2,
+            tx: TransactionId(2),
+            amount: AmountInMinorUnits::from(50),
+        }),
+        Transaction::Dispute(Dispute {
+            client: CLIENT_2,
+            tx: TransactionId(2),
+            amount: None,
+        }),
+    ] {
+        engine.process_transaction(transaction).await.unwrap();
+    }
+    (engine, clients, transactions)
+}
+
+#[tokio::test]
+async fn restored_snapshot_matches_original_state() {
+    // test setup
+    let (engine, live_clients, live_transactions) = run_engine().await;
+    let snapshot = take_snapshot(&live_clients, &live_transactions, engine.sequence())
+        .await
+        .unwrap();
+
+    // test subject
+    let mut clients = InMemoryClientRepository::default();
+    let mut transactions = InMemoryTransactionRepository::default();
+    let sequence = restore_snapshot(snapshot, &mut clients, &mut transactions)
+        .await
+        .unwrap();
+
+    // check results
+    assert_eq!(sequence, 3);
+    verify_replay(&live_clients, &live_transactions, &clients, &transactions)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn snapshot_keeps_transaction_statuses() {
+    // test setup
+    let (engine, clients, transactions) = run_engine().await;
+
+    // test subject
+    let snapshot = take_snapshot(&clients, &transactions, engine.sequence())
+        .await
+        .unwrap();
+
+    // check results
+    let disputed = snapshot
+        .transactions
+        .iter()
+        .find(|transaction| transaction.id == TransactionId(2))
+        .unwrap();
+    assert_eq!(disputed.status, TransactionStatus::Disputed);
+}
+
+#[tokio::test]
+async fn restore_rejects_unsupported_snapshot_version() {
+    // test setup
+    let snapshot = Snapshot {
+        version: SNAPSHOT_VERSION + 1,
+        sequence: 0,
+        clients: vec![],
+        transactions: vec![],
+    };
+
+    // test subject
+    let result = restore_snapshot(
+        snapshot,
+        &mut InMemoryClientRepository::default(),
+        &mut InMemoryTransactionRepository::default(),
+    )
+    .await;
+
+    // check results
+    assert!(matches!(
+        result,
+        Err(SnapshotErrors::UnsupportedVersion(v)) if v == SNAPSHOT_VERSION + 1
+    ));
+}
+
+#[tokio::test]
+async fn snapshot_file_round_trip() {
+    // test setup
+    let path = std::env::temp_dir().join(format!(
+        "payments-engine-snapshot-{}.bin",
+        std::process::id()
+    ));
+    let (engine, clients, transactions) = run_engine().await;
+    let snapshot = take_snapshot(&clients, &transactions, engine.sequence())
+        .await
+        .unwrap();
+
+    // test subject
+    write_snapshot(&path, &snapshot).unwrap();
+    let read = read_snapshot(&path).unwrap();
+
+    // check results
+    fs::remove_file(&path).unwrap();
+    assert_eq!(read, snapshot);
+}
+
+#[test]
+fn snapshot_file_with_other_version_is_refused() {
+    // test setup
+    let path = std::env::temp_dir().join(format!(
+        "payments-engine-snapshot-version-{}.bin",
+        std::process::id()
+    ));
+    let snapshot = Snapshot {
+        version: SNAPSHOT_VERSION + 1,
+        sequence: 0,
+        clients: vec![],
+        transactions: vec![],
+    };
+    write_snapshot(&path, &snapshot).unwrap();
+
+    // test subject
+    let result = read_snapshot(&path);
+
+    // check results
+    fs::remove_file(&path).unwrap();
+    let error = result.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SnapshotErrors>(),
+        Some(SnapshotErrors::UnsupportedVersion(v)) if *v == SNAPSHOT_VERSION + 1
+    ));
+}
+
+#[test]
+fn snapshot_file_without_header_is_refused() {
+    // test setup
+    let path = std::env::temp_dir().join(format!(
+        "payments-engine-snapshot-headerless-{}.bin",
+        std::process::id()
+    ));
+    let snapshot = Snapshot {
+        version: SNAPSHOT_VERSION,
+        sequence: 0,
+        clients: vec![],
+        transactions: vec![],
+    };
+    fs::write(&path, bincode::serialize(&snapshot).unwrap()).unwrap();
+
+    // test subject
+    let result = read_snapshot(&path);
+
+    // check results
+    fs::remove_file(&path).unwrap();
+    let error = result.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SnapshotErrors>(),
+        Some(SnapshotErrors::MissingVersion)
+    ));
+}
+
+#[tokio::test]
+async fn engine_continues_from_restored_balances() {
+    // test setup
+    let (engine, live_clients, live_transactions) = run_engine().await;
+    let snapshot = take_snapshot(&live_clients, &live_transactions, engine.sequence())
+        .await
+        .unwrap();
+    let mut clients = InMemoryClientRepository::default();
+    let mut transactions = InMemoryTransactionRepository::default();
+    let sequence = restore_snapshot(snapshot, &mut clients, &mut transactions)
+        .await
+        .unwrap();
+    let mut engine = TransactionEngine::<InMemoryEngineDeps>::new(
+        clients.clone(),
+        transactions.clone(),
+        InMemoryJournalRepository::default(),
+    )
+    .with_sequence(sequence);
+
+    // test subject
+    engine
+        .process_transaction(Transaction::Withdrawal(Withdrawal {
+            client: CLIENT_1,
+            tx: TransactionId(4),
+            amount: AmountInMinorUnits::from(40),
+        }))
+        .await
+        .unwrap();
+
+    // check results
+    let client = clients.get(&CLIENT_1).await.unwrap();
+    assert_eq!(client.available, AmountInMinorUnits::from(60));
+    let stored = transactions.get(&TransactionId(4)).await.unwrap();
+    assert_eq!(stored.sequence, 4);
+}
+
+#[tokio::test]
+async fn closed_accounts_stay_closed_after_restore() {
+    // test setup
+    let path = std::env::temp_dir().join(format!(
+        "payments-engine-snapshot-closed-{}.bin",
+        std::process::id()
+    ));
+    let mut live_clients = InMemoryClientRepository::default();
+    live_clients
+        .insert(Client {
+            id: CLIENT_1,
+            closed: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let snapshot = take_snapshot(&live_clients, &InMemoryTransactionRepository::default(), 1)
+        .await
+        .unwrap();
+    write_snapshot(&path, &snapshot).unwrap();
+
+    // test subject
+    let mut clients = InMemoryClientRepository::default();
+    restore_snapshot(
+        read_snapshot(&path).unwrap(),
+        &mut clients,
+        &mut InMemoryTransactionRepository::default(),
+    )
+    .await
+    .unwrap();
+
+    // check results
+    fs::remove_file(&path).unwrap();
+    assert!(clients.get(&CLIENT_1).await.unwrap().closed);
+}
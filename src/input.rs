@@ -0,0 +1,222 @@
+use crate::domain::model::{AmountValidation, InputRecord, TimedTransaction};
+use csv::{ReaderBuilder, StringRecord};
+use futures::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use futures::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::io;
+use std::iter;
+use std::rc::Rc;
+use thiserror::Error;
+
+pub type InputResult = Result<TimedTransaction, InputErrors>;
+
+#[derive(Error, Debug)]
+pub enum InputErrors {
+    /// the row was skipped, following rows can still be read
+    #[error("line {}: {}", .0.line, .0.reason)]
+    MalformedRow(MalformedRow),
+    /// the input can't be read any further
+    #[error(transparent)]
+    ReadError(#[from] io::Error),
+}
+
+/// A row which couldn't be turned into a transaction, as written to the rejects report
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct MalformedRow {
+    pub line: u64,
+    pub row: String,
+    pub reason: String,
+}
+
+/// Reads transactions from CSV input with a header row, one transaction per row.
+/// An optional `timestamp` column stamps each transaction with the time its record was created.
+pub fn read_transactions<R: io::Read>(
+    input: R,
+    validation: AmountValidation,
+) -> impl Iterator<Item = InputResult> {
+    let raw = Rc::new(RefCell::new(RawInput::default()));
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        // rows with missing or extra columns are reported like any other malformed row
+        .flexible(true)
+        .from_reader(RecordingReader {
+            inner: input,
+            raw: raw.clone(),
+        });
+    let mut headers: Option<StringRecord> = None;
+    let mut record = StringRecord::new();
+    iter::from_fn(move || loop {
+        match reader.read_record(&mut record) {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(e) => return Some(Err(read_error(e))),
+        }
+        let line = record.position().map_or(0, |position| position.line());
+        let start = record.position().map_or(0, |position| position.byte());
+        let row = raw.borrow_mut().take_row(start, reader.position().byte());
+        match &headers {
+            // the first row names the columns of every following row
+            None => headers = Some(trimmed(record.clone())),
+            Some(columns) => {
+                return Some(parse_row(line, row, record.clone(), columns, validation))
+            }
+        }
+    })
+}
+
+/// Keeps a copy of the bytes read from the input, so rows can be reported exactly as they were
+/// written instead of as parsed fields
+struct RecordingReader<R> {
+    inner: R,
+    raw: Rc<RefCell<RawInput>>,
+}
+
+impl<R: io::Read> io::Read for RecordingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.raw.borrow_mut().bytes.extend_from_slice(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Input bytes which haven't been handed out as a row yet, starting at byte `offset` of the input
+#[derive(Default)]
+struct RawInput {
+    offset: u64,
+    bytes: Vec<u8>,
+}
+
+impl RawInput {
+    /// the row between the `start` and `end` byte positions without line terminators,
+    /// everything before `end` is dropped as rows are read in order
+    fn take_row(&mut self, start: u64, end: u64) -> String {
+        let start = (start.saturating_sub(self.offset) as usize).min(self.bytes.len());
+        let end = (end.saturating_sub(self.offset) as usize).clamp(start, self.bytes.len());
+        let row = String::from_utf8_lossy(&self.bytes[start..end])
+            .trim_matches(&['\r', '\n'][..])
+            .to_string();
+        self.bytes.drain(..end);
+        self.offset += end as u64;
+        row
+    }
+}
+
+/// Async counterpart of `read_transactions`.
+/// Rows are split on line breaks before being parsed, so quoted fields can't span multiple lines.
+pub fn read_transactions_async<R>(
+    input: R,
+    validation: AmountValidation,
+) -> impl Stream<Item = InputResult>
+where
+    R: AsyncRead + Unpin,
+{
+    let lines = BufReader::new(input).lines();
+    stream::unfold(
+        (lines, 0, None::<StringRecord>),
+        move |(mut lines, mut line, mut headers)| async move {
+            loop {
+                let row = match lines.next().await? {
+                    Ok(row) => row,
+                    Err(e) => return Some((Err(e.into()), (lines, line, headers))),
+                };
+                line += 1;
+                if row.trim().is_empty() {
+                    continue;
+                }
+                let record = match split_row(&row) {
+                    Ok(record) => record,
+                    Err(e) => return Some((Err(read_error(e)), (lines, line, headers))),
+                };
+                match &headers {
+                    None => headers = Some(trimmed(record)),
+                    Some(columns) => {
+                        let result = parse_row(line, row, record, columns, validation);
+                        return Some((result, (lines, line, headers)));
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Writes every malformed row to a CSV report, so they can be inspected & fixed after the run
+pub struct RejectsReport<W: io::Write> {
+    writer: csv::Writer<W>,
+    count: u64,
+}
+
+impl<W: io::Write> RejectsReport<W> {
+    pub fn new(writer: W) -> Self {
+        RejectsReport {
+            writer: csv::Writer::from_writer(writer),
+            count: 0,
+        }
+    }
+
+    pub fn record(&mut self, row: &MalformedRow) -> io::Result<()> {
+        self.count += 1;
+        self.writer.serialize(row).map_err(io::Error::from)?;
+        self.writer.flush()
+    }
+
+    /// number of rows recorded so far
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+fn parse_row(
+    line: u64,
+    row: String,
+    record: StringRecord,
+    columns: &StringRecord,
+    validation: AmountValidation,
+) -> InputResult {
+    let malformed = |reason: String| InputErrors::MalformedRow(MalformedRow { line, row, reason });
+    let record: InputRecord = match trimmed(record).deserialize(Some(columns)) {
+        Ok(record) => record,
+        Err(e) => return Err(malformed(deserialize_reason(e))),
+    };
+    record
+        .into_timed_transaction(validation)
+        .map_err(|e| malformed(e.to_string()))
+}
+
+fn split_row(row: &str) -> csv::Result<StringRecord> {
+    let mut record = StringRecord::new();
+    ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(row.as_bytes())
+        .read_record(&mut record)?;
+    Ok(record)
+}
+
+fn trimmed(mut record: StringRecord) -> StringRecord {
+    record.trim();
+    record
+}
+
+/// drops the position from deserialize errors, rows are parsed one at a time so it's always 0
+fn deserialize_reason(error: csv::Error) -> String {
+    match error.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+        _ => error.to_string(),
+    }
+}
+
+/// io errors end the input, anything else only affects the current row
+fn read_error(error: csv::Error) -> InputErrors {
+    if error.is_io_error() {
+        return InputErrors::ReadError(error.into());
+    }
+    InputErrors::MalformedRow(MalformedRow {
+        line: error.position().map_or(0, |position| position.line()),
+        row: String::new(),
+        reason: error.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests;
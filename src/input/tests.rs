@@ -0,0 +1,396 @@
+use crate::domain::model::{
+    AmountInMinorUnits, AmountValidation, Authorize, Capture, Chargeback, ClientId, Close, Deposit,
+    Dispute, ParseError, Timestamp, Transaction, TransactionId, Transfer, Unlock, Void, Withdrawal,
+};
+use crate::input::{
+    read_transactions, read_transactions_async, InputErrors, InputResult, MalformedRow,
+    RejectsReport,
+};
+use futures::io::Cursor;
+use futures::StreamExt;
+
+const INPUT: &str = "type, client, tx, amount
+deposit, 1, 1, 1.5
+withdrawal, 1, 2, 0.5
+
+dispute, 1, 1,
+";
+
+const MALFORMED_INPUT: &str = "type, client, tx, amount
+deposit, 1, 1, 1.5
+refund, 1, 2, 0.5
+deposit, one, 3, 1.0
+withdrawal, 1, 4,
+deposit, 1, 5
+dispute, 1, 1,
+";
+
+fn expected_transactions() -> Vec<Transaction> {
+    vec![
+        Transaction::Deposit(Deposit {
+            client: ClientId(1),
+            tx: TransactionId(1),
+            amount: "1.5".parse::<AmountInMinorUnits>().unwrap(),
+        }),
+        Transaction::Withdrawal(Withdrawal {
+            client: ClientId(1),
+            tx: TransactionId(2),
+            amount: "0.5".parse::<AmountInMinorUnits>().unwrap(),
+        }),
+        Transaction::Dispute(Dispute {
+            client: ClientId(1),
+            tx: TransactionId(1),
+            amount: None,
+        }),
+    ]
+}
+
+/// splits read results into the valid transactions and the rejected rows
+fn partition(results: Vec<InputResult>) -> (Vec<Transaction>, Vec<MalformedRow>) {
+    let mut transactions = Vec::new();
+    let mut rejected = Vec::new();
+    for result in results {
+        match result {
+            Ok(record) => transactions.push(record.transaction),
+            Err(InputErrors::MalformedRow(row)) => rejected.push(row),
+            Err(e) => panic!("unexpected read error {}", e),
+        }
+    }
+    (transactions, rejected)
+}
+
+#[test]
+fn transactions_are_read_from_any_reader() {
+    // test subject
+    let (transactions, rejected) =
+        partition(read_transactions(INPUT.as_bytes(), AmountValidation::Strict).collect());
+
+    // check results
+    assert_eq!(transactions, expected_transactions());
+    assert!(rejected.is_empty());
+}
+
+#[tokio::test]
+async fn transactions_are_read_from_async_reader() {
+    // test subject
+    let (transactions, rejected) = partition(
+        read_transactions_async(Cursor::new(INPUT), AmountValidation::Strict)
+            .collect()
+            .await,
+    );
+
+    // check results
+    assert_eq!(transactions, expected_transactions());
+    assert!(rejected.is_empty());
+}
+
+#[tokio::test]
+async fn async_reader_handles_input_without_trailing_newline() {
+    // test setup
+    let input = INPUT.trim_end();
+
+    // test subject
+    let (transactions, _) = partition(
+        read_transactions_async(Cursor::new(input), AmountValidation::Strict)
+            .collect()
+            .await,
+    );
+
+    // check results
+    assert_eq!(transactions, expected_transactions());
+}
+
+#[test]
+fn malformed_rows_are_skipped() {
+    // test subject
+    let (transactions, rejected) = partition(
+        read_transactions(MALFORMED_INPUT.as_bytes(), AmountValidation::Strict).collect(),
+    );
+
+    // check results
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(
+        rejected.iter().map(|row| row.line).collect::<Vec<_>>(),
+        vec![3, 4, 5, 6]
+    );
+}
+
+#[test]
+fn malformed_row_keeps_raw_row_contents() {
+    // test subject
+    let (_, rejected) = partition(
+        read_transactions(MALFORMED_INPUT.as_bytes(), AmountValidation::Strict).collect(),
+    );
+
+    // check results
+    assert_eq!(rejected[0].row, "refund, 1, 2, 0.5");
+}
+
+#[tokio::test]
+async fn quoted_row_is_reported_as_written() {
+    // test setup
+    let row = r#""deposit, x",1,2,"1,5""#;
+    let input = format!("type, client, tx, amount\n{}\ndeposit, 1, 3, 1.0\n", row);
+
+    // test subject
+    let (_, rejected) =
+        partition(read_transactions(input.as_bytes(), AmountValidation::Strict).collect());
+    let (_, rejected_async) = partition(
+        read_transactions_async(Cursor::new(input.clone()), AmountValidation::Strict)
+            .collect()
+            .await,
+    );
+
+    // check results
+    assert_eq!(rejected.len(), 1);
+    assert_eq!(rejected[0].line, 2);
+    assert_eq!(rejected[0].row, row);
+    assert_eq!(rejected_async, rejected);
+}
+
+#[tokio::test]
+async fn async_reader_skips_the_same_malformed_rows() {
+    // test setup
+    let (expected_transactions, expected_rejected) = partition(
+        read_transactions(MALFORMED_INPUT.as_bytes(), AmountValidation::Strict).collect(),
+    );
+
+    // test subject
+    let (transactions, rejected) = partition(
+        read_transactions_async(Cursor::new(MALFORMED_INPUT), AmountValidation::Strict)
+            .collect()
+            .await,
+    );
+
+    // check results
+    assert_eq!(transactions, expected_transactions);
+    assert_eq!(rejected, expected_rejected);
+}
+
+#[test]
+fn rejects_report_lists_line_row_and_reason() {
+    // test setup
+    let mut output = Vec::new();
+    let mut report = RejectsReport::new(&mut output);
+
+    // test subject
+    report
+        .record(&MalformedRow {
+            line: 3,
+            row: "refund, 1, 2, 0.5".to_string(),
+            reason: "invalid transaction record".to_string(),
+        })
+        .unwrap();
+
+    // check results
+    assert_eq!(report.count(), 1);
+    drop(report);
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "line,row,reason\n3,\"refund, 1, 2, 0.5\",invalid transaction record\n"
+    );
+}
+
+#[test]
+fn malformed_rows_explain_what_is_wrong() {
+    // test setup
+    let input = "type, client, tx, amount
+refund, 1, 2, 0.5
+deposit, one, 3, 1.0
+deposit, 1, -4, 1.0
+withdrawal, 1, 5,
+deposit, 1, 6, 1.0a
+dispute, 1, 1, -1.0
+";
+
+    // test subject
+    let (_, rejected) =
+        partition(read_transactions(input.as_bytes(), AmountValidation::Strict).collect());
+
+    // check results
+    assert_eq!(
+        rejected
+            .into_iter()
+            .map(|row| row.reason)
+            .collect::<Vec<_>>(),
+        vec![
+            ParseError::UnknownType("refund".to_string()).to_string(),
+            ParseError::InvalidClientId("one".to_string()).to_string(),
+            ParseError::InvalidTxId("-4".to_string()).to_string(),
+            ParseError::MissingAmount.to_string(),
+            ParseError::InvalidAmount("1.0a".to_string()).to_string(),
+            ParseError::NonPositiveAmount("-1.0".to_string()).to_string(),
+        ]
+    );
+}
+
+#[test]
+fn optional_timestamp_column_is_read_with_each_transaction() {
+    // test setup
+    let input = "type, client, tx, amount, timestamp
+deposit, 1, 1, 1.5, 1700000000
+dispute, 1, 1, ,
+deposit, 1, 2, 1.0, yesterday
+";
+
+    // test subject
+    let results: Vec<InputResult> =
+        read_transactions(input.as_bytes(), AmountValidation::Strict).collect();
+
+    // check results
+    let timestamps = results
+        .iter()
+        .map(|result| match result {
+            Ok(record) => Ok(record.timestamp),
+            Err(InputErrors::MalformedRow(row)) => Err(row.reason.clone()),
+            Err(e) => panic!("unexpected read error {}", e),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        timestamps,
+        vec![
+            Ok(Some(Timestamp(1_700_000_000))),
+            Ok(None),
+            Err(ParseError::InvalidTimestamp("yesterday".to_string()).to_string()),
+        ]
+    );
+}
+
+#[test]
+fn dispute_family_records_accept_an_optional_amount() {
+    // test setup
+    let input = "type, client, tx, amount
+dispute, 1, 1, 0.25
+chargeback, 1, 1,
+";
+
+    // test subject
+    let (transactions, rejected) =
+        partition(read_transactions(input.as_bytes(), AmountValidation::Strict).collect());
+
+    // check results
+    assert!(rejected.is_empty());
+    assert_eq!(
+        transactions,
+        vec![
+            Transaction::Dispute(Dispute {
+                client: ClientId(1),
+                tx: TransactionId(1),
+                amount: Some("0.25".parse::<AmountInMinorUnits>().unwrap()),
+            }),
+            Transaction::Chargeback(Chargeback {
+                client: ClientId(1),
+                tx: TransactionId(1),
+                amount: None,
+            }),
+        ]
+    );
+}
+
+#[test]
+fn account_operations_are_read_with_their_operator() {
+    // test setup
+    let input = "type, client, tx, amount, operator
+unlock, 1, 2, , alice
+close, 1, 3, , bob
+freeze, 1, 4, ,
+close, 1, 5, 1.0, bob
+";
+
+    // test subject
+    let (transactions, rejected) =
+        partition(read_transactions(input.as_bytes(), AmountValidation::Strict).collect());
+
+    // check results
+    assert_eq!(
+        transactions,
+        vec![
+            Transaction::Unlock(Unlock {
+                client: ClientId(1),
+                tx: TransactionId(2),
+                operator: "alice".to_string(),
+            }),
+            Transaction::Close(Close {
+                client: ClientId(1),
+                tx: TransactionId(3),
+                operator: "bob".to_string(),
+            }),
+        ]
+    );
+    assert_eq!(
+        rejected
+            .iter()
+            .map(|row| row.reason.clone())
+            .collect::<Vec<_>>(),
+        vec![
+            ParseError::MissingOperator.to_string(),
+            ParseError::UnexpectedAmount("1.0".to_string()).to_string(),
+        ]
+    );
+}
+
+#[test]
+fn transfers_are_read_with_their_recipient() {
+    // test setup
+    let input = "type, client, tx, amount, to_client
+transfer, 1, 2, 1.5, 2
+transfer, 1, 3, 1.5,
+";
+
+    // test subject
+    let (transactions, rejected) =
+        partition(read_transactions(input.as_bytes(), AmountValidation::Strict).collect());
+
+    // check results
+    assert_eq!(
+        transactions,
+        vec![Transaction::Transfer(Transfer {
+            from: ClientId(1),
+            to: ClientId(2),
+            tx: TransactionId(2),
+            amount: "1.5".parse::<AmountInMinorUnits>().unwrap(),
+        })]
+    );
+    assert_eq!(rejected.len(), 1);
+    assert_eq!(rejected[0].reason, ParseError::MissingRecipient.to_string());
+}
+
+#[test]
+fn authorizations_are_read_with_their_captures_and_voids() {
+    // test setup
+    let input = "type, client, tx, amount
+authorize, 1, 2, 1.5
+capture, 1, 2,
+void, 1, 3,
+capture, 1, 4, 1.5
+";
+
+    // test subject
+    let (transactions, rejected) =
+        partition(read_transactions(input.as_bytes(), AmountValidation::Strict).collect());
+
+    // check results
+    assert_eq!(
+        transactions,
+        vec![
+            Transaction::Authorize(Authorize {
+                client: ClientId(1),
+                tx: TransactionId(2),
+                amount: "1.5".parse::<AmountInMinorUnits>().unwrap(),
+            }),
+            Transaction::Capture(Capture {
+                client: ClientId(1),
+                tx: TransactionId(2),
+            }),
+            Transaction::Void(Void {
+                client: ClientId(1),
+                tx: TransactionId(3),
+            }),
+        ]
+    );
+    assert_eq!(rejected.len(), 1);
+    assert_eq!(
+        rejected[0].reason,
+        ParseError::UnexpectedAmount("1.5".to_string()).to_string()
+    );
+}
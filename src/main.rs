@@ -1,16 +1,31 @@
 mod adapters;
 mod domain;
+mod input;
+mod runner;
 
-use crate::adapters::memory::InMemoryEngineDeps;
+use crate::adapters::file::{
+    read_fee_schedule, read_overdraft_limits, read_snapshot, write_snapshot, FileJournalEngineDeps,
+    FileJournalRepository,
+};
+use crate::adapters::memory::{
+    InMemoryClientRepository, InMemoryEngineDeps, InMemoryJournalRepository,
+    InMemoryTransactionRepository,
+};
 use crate::domain::engine::TransactionEngine;
-use crate::domain::model::{InputRecord, Transaction};
+use crate::domain::model::{
+    AmountInMinorUnits, AmountValidation, AuthorizationExpiry, Client, ClientId, DisputeLifecycle,
+    DisputeWindow, DuplicatePolicy, FeeSchedule, LockPolicy, OverdraftPolicy, Transaction,
+    TransactionOutcome,
+};
 use crate::domain::ports::{Engine, EngineConfig};
-use clap::{App, Arg};
-use csv::{ReaderBuilder, Trim};
+use crate::domain::replay::{replay_journal, verify_replay};
+use crate::domain::snapshot::{restore_snapshot, take_snapshot};
+use crate::input::RejectsReport;
+use crate::runner::{process_reader, OutcomeReporter, ShardedRunner};
+use clap::{App, Arg, ArgMatches};
 use futures::StreamExt;
-use std::convert::TryInto;
+use std::fs::File;
 use std::io;
-use std::path::PathBuf;
 
 #[tokio::main]
 async fn main() {
@@ -19,9 +34,166 @@ async fn main() {
         .author("Brandon K. <brandonkite92@gmail.com>")
         .arg(
             Arg::with_name("TRANSACTIONS_FILE")
-                .help("A file containing the transactions")
+                .help("A file containing the transactions, or - to read them from stdin")
                 .required(true),
         )
+        .arg(
+            Arg::with_name("ALLOW_WHEN_LOCKED")
+                .long("allow-when-locked")
+                .help("Transaction types still applied to locked accounts [default: capture, void, resolve, chargeback]")
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .possible_values(&["deposit", "withdrawal", "transfer", "authorize", "capture", "void", "dispute", "resolve", "chargeback"]),
+        )
+        .arg(
+            Arg::with_name("VERBOSE")
+                .short("v")
+                .long("verbose")
+                .help("Report rejected and ignored transactions on stderr"),
+        )
+        .arg(
+            Arg::with_name("JOURNAL")
+                .long("journal")
+                .value_name("FILE")
+                .help("Append-only journal of applied transactions, existing entries are replayed on start")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("VERIFY_JOURNAL")
+                .long("verify-journal")
+                .help("Rebuild state from the journal after processing and check it matches")
+                .requires("JOURNAL"),
+        )
+        .arg(
+            Arg::with_name("SNAPSHOT_IN")
+                .long("snapshot-in")
+                .value_name("FILE")
+                .help("Restore clients and transactions from a snapshot before processing")
+                .takes_value(true)
+                // the journal already restores the full state on its own
+                .conflicts_with("JOURNAL"),
+        )
+        .arg(
+            Arg::with_name("SNAPSHOT_OUT")
+                .long("snapshot-out")
+                .value_name("FILE")
+                .help("Write a snapshot of clients and transactions after processing")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("SHARDS")
+                .long("shards")
+                .value_name("N")
+                .help("Process clients in parallel across N engines, once records span clients of different engines the rest of the input goes through a single one [default: 1]")
+                .takes_value(true)
+                .validator(|n| match n.parse::<usize>() {
+                    Ok(n) if n > 0 => Ok(()),
+                    _ => Err("expected a positive number".to_string()),
+                })
+                // every shard keeps its own state, which isn't journaled or snapshotted
+                // and would credit fees to its own copy of the house account
+                .conflicts_with_all(&["JOURNAL", "SNAPSHOT_IN", "SNAPSHOT_OUT", "FEE_SCHEDULE"]),
+        )
+        .arg(
+            Arg::with_name("OVERDRAFT_LIMIT")
+                .long("overdraft-limit")
+                .value_name("AMOUNT")
+                .help("Let every client overdraw their available funds by up to AMOUNT")
+                .takes_value(true)
+                .validator(|amount| {
+                    amount
+                        .parse::<AmountInMinorUnits>()
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                }),
+        )
+        .arg(
+            Arg::with_name("OVERDRAFT_LIMITS")
+                .long("overdraft-limits")
+                .value_name("FILE")
+                .help("CSV file with a `client` and `limit` column setting each client's overdraft limit")
+                .takes_value(true)
+                .conflicts_with("OVERDRAFT_LIMIT"),
+        )
+        .arg(
+            Arg::with_name("FEE_SCHEDULE")
+                .long("fee-schedule")
+                .value_name("FILE")
+                .help("CSV file with a `type`, `from`, `flat`, `percent`, `min` and `max` column, one row per fee tier charged on withdrawals, captured authorizations & chargebacks")
+                .takes_value(true)
+                .requires("HOUSE_ACCOUNT"),
+        )
+        .arg(
+            Arg::with_name("HOUSE_ACCOUNT")
+                .long("house-account")
+                .value_name("CLIENT")
+                .help("Client the fees from --fee-schedule are credited to")
+                .takes_value(true)
+                .requires("FEE_SCHEDULE")
+                .validator(|id| {
+                    id.parse::<ClientId>()
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                }),
+        )
+        .arg(
+            Arg::with_name("IDEMPOTENT_RETRIES")
+                .long("idempotent-retries")
+                .help("Ignore exact copies of already processed deposits and withdrawals instead of rejecting them"),
+        )
+        .arg(
+            Arg::with_name("MAX_DISPUTES")
+                .long("max-disputes")
+                .value_name("N")
+                .help("How often a transaction can be disputed, resolved funds can be disputed again until then [default: 1]")
+                .takes_value(true)
+                .validator(|n| match n.parse::<u32>() {
+                    Ok(n) if n > 0 => Ok(()),
+                    _ => Err("expected a positive number".to_string()),
+                }),
+        )
+        .arg(
+            Arg::with_name("DISPUTE_WINDOW")
+                .long("dispute-window")
+                .value_name("AGE")
+                .help("Reject disputes of transactions older than AGE, in seconds if the input has a timestamp column or in number of records otherwise")
+                .takes_value(true)
+                .validator(validate_age),
+        )
+        .arg(
+            Arg::with_name("RESOLUTION_DEADLINE")
+                .long("resolution-deadline")
+                .value_name("AGE")
+                .help("Automatically resolve disputes still open after AGE, measured like --dispute-window")
+                .takes_value(true)
+                .validator(validate_age),
+        )
+        .arg(
+            Arg::with_name("AUTHORIZATION_EXPIRY")
+                .long("authorization-expiry")
+                .value_name("AGE")
+                .help("Automatically void authorizations not captured within AGE, measured like --dispute-window")
+                .takes_value(true)
+                .validator(validate_age),
+        )
+        .arg(
+            Arg::with_name("LENIENT_AMOUNTS")
+                .long("lenient-amounts")
+                .help("Round amounts with more than four decimal places instead of rejecting the row"),
+        )
+        .arg(
+            Arg::with_name("REJECTS")
+                .long("rejects")
+                .value_name("FILE")
+                .help("Write malformed rows to a CSV report [default: stderr]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("FAIL_ON_REJECT")
+                .long("fail-on-reject")
+                .help("Exit with status 2 if any row was malformed, instead of processing on a best effort basis"),
+        )
         .get_matches();
 
     let file = matches
@@ -29,26 +201,302 @@ async fn main() {
         // We shouldn't reach this due to usage of `.required(true)` above
         .expect("No transactions file input provided.");
 
-    let mut engine = TransactionEngine::<InMemoryEngineDeps>::default();
-    process_file(file, &mut engine).await;
-    print_clients_csv(&mut engine).await;
+    let lock_policy = match matches.values_of("ALLOW_WHEN_LOCKED") {
+        Some(tx_types) => LockPolicy::allowing(tx_types)
+            // clap already restricts the values to known transaction types
+            .expect("Invalid transaction type for locked account policy."),
+        None => LockPolicy::default(),
+    };
+    let overdraft_policy = match (
+        matches.value_of("OVERDRAFT_LIMIT"),
+        matches.value_of("OVERDRAFT_LIMITS"),
+    ) {
+        (Some(limit), _) => OverdraftPolicy::Fixed(
+            limit
+                .parse()
+                // the amount was already validated by clap
+                .expect("Invalid overdraft limit."),
+        ),
+        (None, Some(path)) => read_overdraft_limits(path).unwrap(),
+        (None, None) => OverdraftPolicy::default(),
+    };
+    let duplicate_policy = if matches.is_present("IDEMPOTENT_RETRIES") {
+        DuplicatePolicy::Idempotent
+    } else {
+        DuplicatePolicy::Reject
+    };
+    let dispute_lifecycle = match matches.value_of("MAX_DISPUTES") {
+        Some(n) => DisputeLifecycle::new(n.parse().expect("Invalid maximum number of disputes.")),
+        None => DisputeLifecycle::default(),
+    };
+    let dispute_window = DisputeWindow::new(
+        matches
+            .value_of("DISPUTE_WINDOW")
+            .map(|age| age.parse().expect("Invalid dispute window.")),
+        matches
+            .value_of("RESOLUTION_DEADLINE")
+            .map(|age| age.parse().expect("Invalid resolution deadline.")),
+    );
+    let authorization_expiry = AuthorizationExpiry::new(
+        matches
+            .value_of("AUTHORIZATION_EXPIRY")
+            .map(|age| age.parse().expect("Invalid authorization expiry.")),
+    );
+    let fee_schedule = match matches.value_of("FEE_SCHEDULE") {
+        Some(path) => {
+            let house_account = matches
+                .value_of("HOUSE_ACCOUNT")
+                .and_then(|id| id.parse().ok())
+                // the house account was already validated by clap
+                .expect("Invalid house account.");
+            read_fee_schedule(path, house_account).unwrap()
+        }
+        None => FeeSchedule::default(),
+    };
+    let policies = EnginePolicies {
+        lock: lock_policy,
+        overdraft: overdraft_policy,
+        duplicates: duplicate_policy,
+        disputes: dispute_lifecycle,
+        dispute_window,
+        authorization_expiry,
+        fees: fee_schedule,
+    };
+
+    let verbose = matches.is_present("VERBOSE");
+    let shards = matches
+        .value_of("SHARDS")
+        .map(|n| n.parse::<usize>().expect("Invalid number of shards."))
+        .unwrap_or(1);
+    let validation = if matches.is_present("LENIENT_AMOUNTS") {
+        AmountValidation::Lenient
+    } else {
+        AmountValidation::Strict
+    };
+    let mut rejects = RejectsReport::new(open_rejects(matches.value_of("REJECTS")));
+    if shards > 1 {
+        process_sharded(file, validation, shards, &policies, verbose, &mut rejects).await;
+    } else {
+        process_sequential(&matches, file, validation, &policies, verbose, &mut rejects).await;
+    }
+
+    if rejects.count() > 0 && matches.is_present("FAIL_ON_REJECT") {
+        eprintln!("{} malformed rows were rejected", rejects.count());
+        std::process::exit(2);
+    }
 }
 
-async fn process_file<C: EngineConfig>(file_path: &str, engine: &mut TransactionEngine<C>) {
-    let mut rdr = ReaderBuilder::new()
-        .trim(Trim::All)
-        .from_path(PathBuf::from(file_path))
-        .unwrap();
-    for result in rdr.deserialize() {
-        let record: InputRecord = result.unwrap();
-        let transaction: Transaction = record.try_into().unwrap();
+/// Business rules configured on the command line, shared by every engine that gets created
+struct EnginePolicies {
+    lock: LockPolicy,
+    overdraft: OverdraftPolicy,
+    duplicates: DuplicatePolicy,
+    disputes: DisputeLifecycle,
+    dispute_window: DisputeWindow,
+    authorization_expiry: AuthorizationExpiry,
+    fees: FeeSchedule,
+}
+
+impl EnginePolicies {
+    fn apply<C: EngineConfig>(&self, engine: TransactionEngine<C>) -> TransactionEngine<C> {
         engine
-            .process_transaction(transaction.clone())
+            .with_lock_policy(self.lock.clone())
+            .with_overdraft_policy(self.overdraft.clone())
+            .with_duplicate_policy(self.duplicates)
+            .with_dispute_lifecycle(self.disputes.clone())
+            .with_dispute_window(self.dispute_window.clone())
+            .with_authorization_expiry(self.authorization_expiry.clone())
+            .with_fee_schedule(self.fees.clone())
+    }
+}
+
+async fn process_sequential(
+    matches: &ArgMatches<'_>,
+    file: &str,
+    validation: AmountValidation,
+    policies: &EnginePolicies,
+    verbose: bool,
+    rejects: &mut RejectsReport<Box<dyn io::Write>>,
+) {
+    let clients = InMemoryClientRepository::default();
+    let transactions = InMemoryTransactionRepository::default();
+    let snapshot_out = matches.value_of("SNAPSHOT_OUT");
+    match matches.value_of("JOURNAL") {
+        Some(journal_path) => {
+            let journal = FileJournalRepository::open(journal_path).unwrap_or_else(|e| {
+                eprintln!("failed to open journal {}: {}", journal_path, e);
+                std::process::exit(1);
+            });
+            // restore the state recorded by previous runs before processing new transactions
+            let sequence =
+                replay_journal(&journal, &mut clients.clone(), &mut transactions.clone())
+                    .await
+                    .unwrap_or_else(|e| {
+                        eprintln!("failed to replay journal {}: {}", journal_path, e);
+                        std::process::exit(1);
+                    });
+
+            let engine = TransactionEngine::<FileJournalEngineDeps>::new(
+                clients.clone(),
+                transactions.clone(),
+                journal.clone(),
+            )
+            .with_sequence(sequence);
+            let mut engine = policies.apply(engine);
+            process_reader(
+                open_input(file),
+                validation,
+                &mut engine,
+                reporter(verbose),
+                rejects,
+            )
+            .await
+            .unwrap();
+            if matches.is_present("VERIFY_JOURNAL") {
+                verify_journal(&journal, &clients, &transactions).await;
+            }
+            if let Some(snapshot_path) = snapshot_out {
+                save_snapshot(snapshot_path, &clients, &transactions, engine.sequence()).await;
+            }
+            print_clients_csv(&mut engine).await;
+        }
+        None => {
+            let sequence = match matches.value_of("SNAPSHOT_IN") {
+                Some(snapshot_path) => {
+                    let snapshot = read_snapshot(snapshot_path).unwrap_or_else(|e| {
+                        eprintln!("{:#}", e);
+                        std::process::exit(1);
+                    });
+                    restore_snapshot(snapshot, &mut clients.clone(), &mut transactions.clone())
+                        .await
+                        .unwrap()
+                }
+                None => 0,
+            };
+
+            let engine = TransactionEngine::<InMemoryEngineDeps>::new(
+                clients.clone(),
+                transactions.clone(),
+                InMemoryJournalRepository::default(),
+            )
+            .with_sequence(sequence);
+            let mut engine = policies.apply(engine);
+            process_reader(
+                open_input(file),
+                validation,
+                &mut engine,
+                reporter(verbose),
+                rejects,
+            )
             .await
             .unwrap();
+            if let Some(snapshot_path) = snapshot_out {
+                save_snapshot(snapshot_path, &clients, &transactions, engine.sequence()).await;
+            }
+            print_clients_csv(&mut engine).await;
+        }
+    }
+}
+
+async fn process_sharded(
+    input_path: &str,
+    validation: AmountValidation,
+    shards: usize,
+    policies: &EnginePolicies,
+    verbose: bool,
+    rejects: &mut RejectsReport<Box<dyn io::Write>>,
+) {
+    let engines = (0..shards)
+        .map(|_| policies.apply(TransactionEngine::<InMemoryEngineDeps>::default()))
+        .collect();
+    let mut runner = ShardedRunner::new(engines, reporter(verbose));
+    runner
+        .submit_reader(open_input(input_path), validation, rejects)
+        .await
+        .unwrap();
+    let clients = runner.finish().await.unwrap();
+    write_clients_csv(clients);
+}
+
+fn validate_age(age: String) -> Result<(), String> {
+    age.parse::<u64>()
+        .map(|_| ())
+        .map_err(|_| "expected a whole number".to_string())
+}
+
+/// opens the transactions file, `-` reads from stdin instead so the engine can sit in a pipeline
+fn open_input(path: &str) -> Box<dyn io::Read> {
+    match path {
+        "-" => Box::new(io::stdin()),
+        path => Box::new(File::open(path).unwrap()),
     }
 }
 
+fn open_rejects(path: Option<&str>) -> Box<dyn io::Write> {
+    match path {
+        Some(path) => Box::new(File::create(path).unwrap()),
+        None => Box::new(io::stderr()),
+    }
+}
+
+fn reporter(verbose: bool) -> OutcomeReporter {
+    if verbose {
+        report_outcome
+    } else {
+        |_, _| {}
+    }
+}
+
+fn report_outcome(transaction: &Transaction, outcome: &TransactionOutcome) {
+    let (status, reason) = match outcome {
+        TransactionOutcome::Applied => return,
+        TransactionOutcome::Rejected { reason } => ("rejected", reason),
+        TransactionOutcome::Ignored { reason } => ("ignored", reason),
+    };
+    eprintln!(
+        "{} tx {} for client {}: {}",
+        status,
+        transaction.tx().0,
+        transaction.client().0,
+        reason
+    );
+}
+
+async fn verify_journal(
+    journal: &FileJournalRepository,
+    clients: &InMemoryClientRepository,
+    transactions: &InMemoryTransactionRepository,
+) {
+    let mut replayed_clients = InMemoryClientRepository::default();
+    let mut replayed_transactions = InMemoryTransactionRepository::default();
+    replay_journal(journal, &mut replayed_clients, &mut replayed_transactions)
+        .await
+        .unwrap();
+    if let Err(e) = verify_replay(
+        clients,
+        transactions,
+        &replayed_clients,
+        &replayed_transactions,
+    )
+    .await
+    {
+        eprintln!("journal verification failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn save_snapshot(
+    path: &str,
+    clients: &InMemoryClientRepository,
+    transactions: &InMemoryTransactionRepository,
+    sequence: u64,
+) {
+    let snapshot = take_snapshot(clients, transactions, sequence)
+        .await
+        .unwrap();
+    write_snapshot(path, &snapshot).unwrap();
+}
+
 async fn print_clients_csv<C: EngineConfig>(engine: &mut TransactionEngine<C>) {
     let mut wtr = csv::Writer::from_writer(io::stdout());
 
@@ -58,3 +506,11 @@ async fn print_clients_csv<C: EngineConfig>(engine: &mut TransactionEngine<C>) {
     }
     wtr.flush().unwrap();
 }
+
+fn write_clients_csv(clients: Vec<Client>) {
+    let mut wtr = csv::Writer::from_writer(io::stdout());
+    for client in clients {
+        wtr.serialize(client).unwrap();
+    }
+    wtr.flush().unwrap();
+}
@@ -0,0 +1,274 @@
+use crate::domain::engine::TransactionEngine;
+use crate::domain::model::{
+    AmountValidation, Client, ClientId, RecordTime, TimedTransaction, Transaction, TransactionId,
+    TransactionOutcome,
+};
+use crate::domain::ports::{Engine, EngineConfig, EngineErrors};
+use crate::input::{read_transactions, read_transactions_async, InputErrors, RejectsReport};
+use futures::channel::mpsc;
+use futures::io::AsyncRead;
+use futures::{SinkExt, StreamExt, TryStreamExt};
+use std::collections::HashMap;
+use std::io;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+
+// number of transactions buffered per shard before submitting waits on the worker
+const SHARD_QUEUE_SIZE: usize = 1024;
+
+pub type OutcomeReporter = fn(&Transaction, &TransactionOutcome);
+
+// a worker hands its engine back once its queue is drained, so the clients can be collected
+type Worker<T> = JoinHandle<Result<TransactionEngine<T>, EngineErrors>>;
+
+/// Feeds every transaction read from `input` through the engine, in input order.
+/// Malformed rows are skipped and recorded in `rejects`.
+pub async fn process_reader<R, T, W>(
+    input: R,
+    validation: AmountValidation,
+    engine: &mut TransactionEngine<T>,
+    report: OutcomeReporter,
+    rejects: &mut RejectsReport<W>,
+) -> Result<(), RunnerErrors>
+where
+    R: io::Read,
+    T: EngineConfig,
+    W: io::Write,
+{
+    for result in read_transactions(input, validation) {
+        if let Some(record) = accept_row(result, rejects)? {
+            let outcome = engine.process_timed_transaction(record.clone()).await?;
+            report(&record.transaction, &outcome);
+        }
+    }
+    Ok(())
+}
+
+/// Same as `process_reader`, for sources which shouldn't block the runtime while waiting on input
+// not used by the CLI, which reads from files & stdin
+#[allow(dead_code)]
+pub async fn process_async_reader<R, T, W>(
+    input: R,
+    validation: AmountValidation,
+    engine: &mut TransactionEngine<T>,
+    report: OutcomeReporter,
+    rejects: &mut RejectsReport<W>,
+) -> Result<(), RunnerErrors>
+where
+    R: AsyncRead + Unpin,
+    T: EngineConfig,
+    W: io::Write,
+{
+    let mut rows = read_transactions_async(input, validation).boxed_local();
+    while let Some(result) = rows.next().await {
+        if let Some(record) = accept_row(result, rejects)? {
+            let outcome = engine.process_timed_transaction(record.clone()).await?;
+            report(&record.transaction, &outcome);
+        }
+    }
+    Ok(())
+}
+
+/// records malformed rows so processing can carry on with the next one
+fn accept_row<W: io::Write>(
+    result: Result<TimedTransaction, InputErrors>,
+    rejects: &mut RejectsReport<W>,
+) -> Result<Option<TimedTransaction>, RunnerErrors> {
+    match result {
+        Ok(record) => Ok(Some(record)),
+        Err(InputErrors::MalformedRow(row)) => {
+            rejects.record(&row)?;
+            Ok(None)
+        }
+        Err(InputErrors::ReadError(e)) => Err(e.into()),
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RunnerErrors {
+    #[error(transparent)]
+    EngineError(#[from] EngineErrors),
+    #[error(transparent)]
+    InputError(#[from] io::Error),
+    #[error("worker for shard {0} stopped unexpectedly")]
+    WorkerStopped(usize),
+}
+
+/// Processes transactions in parallel by routing each client to one of several engines.
+///
+/// All balance effects are scoped to a single client, so as long as every transaction of a client
+/// goes through the same engine in input order, the resulting balances match a sequential run.
+/// Records are numbered across all shards, so ages measured in records match a sequential run too.
+/// Each shard keeps its own transaction records though, so once a record reuses or references a
+/// transaction id first used on another shard, or transfers funds to a client on another shard,
+/// the shards are merged into a single engine which processes the rest of the input sequentially.
+pub struct ShardedRunner<T: EngineConfig> {
+    queues: Vec<mpsc::Sender<TimedTransaction>>,
+    workers: Vec<Option<Worker<T>>>,
+    report: OutcomeReporter,
+    // when the last submitted record was created, counting records across all shards
+    now: RecordTime,
+    // the shard each transaction id was first used on
+    owners: HashMap<TransactionId, usize>,
+    // the engine processing all records once the shards were merged
+    merged: Option<TransactionEngine<T>>,
+}
+
+impl<T> ShardedRunner<T>
+where
+    T: EngineConfig + 'static,
+{
+    /// spawns one worker task per engine, `report` is called with the outcome of every transaction
+    pub fn new(engines: Vec<TransactionEngine<T>>, report: OutcomeReporter) -> Self {
+        assert!(!engines.is_empty(), "at least one shard is required");
+        let (queues, workers) = engines
+            .into_iter()
+            .map(|engine| {
+                let (sender, receiver) = mpsc::channel(SHARD_QUEUE_SIZE);
+                let worker = tokio::spawn(run_shard(engine, receiver, report));
+                (sender, Some(worker))
+            })
+            .unzip();
+        ShardedRunner {
+            queues,
+            workers,
+            report,
+            now: RecordTime::default(),
+            owners: HashMap::new(),
+            merged: None,
+        }
+    }
+
+    /// queues the transaction on the shard owning its client
+    pub async fn submit(&mut self, mut record: TimedTransaction) -> Result<(), RunnerErrors> {
+        // ages are measured in records of the whole input, the same as in a sequential run
+        self.now = RecordTime {
+            sequence: self.now.sequence + 1,
+            timestamp: record.timestamp,
+        };
+        record.sequence = Some(self.now.sequence);
+        if self.merged.is_none() {
+            let shard = shard_for(record.transaction.client(), self.queues.len());
+            // only the shard which saw an id first can tell whether it's taken, or what it refers to
+            let owner = *self.owners.entry(record.transaction.tx()).or_insert(shard);
+            // and a transfer has to be applied to the accounts of both clients at once
+            let counterparty = record
+                .transaction
+                .counterparty()
+                .map(|client| shard_for(client, self.queues.len()));
+            if owner == shard && counterparty.is_none_or(|other| other == shard) {
+                return self.queue(shard, record).await;
+            }
+            self.merge().await?;
+        }
+        let engine = self.merged.as_mut().expect("shards were merged");
+        let outcome = engine.process_timed_transaction(record.clone()).await?;
+        (self.report)(&record.transaction, &outcome);
+        Ok(())
+    }
+
+    async fn queue(&mut self, shard: usize, record: TimedTransaction) -> Result<(), RunnerErrors> {
+        if self.queues[shard].send(record).await.is_err() {
+            // the worker only hangs up early when its engine failed
+            return Err(self.stopped_worker_error(shard).await);
+        }
+        Ok(())
+    }
+
+    /// queues every transaction read from `input`, malformed rows are skipped and recorded in `rejects`
+    pub async fn submit_reader<R, W>(
+        &mut self,
+        input: R,
+        validation: AmountValidation,
+        rejects: &mut RejectsReport<W>,
+    ) -> Result<(), RunnerErrors>
+    where
+        R: io::Read,
+        W: io::Write,
+    {
+        for result in read_transactions(input, validation) {
+            if let Some(record) = accept_row(result, rejects)? {
+                self.submit(record).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// waits for all queued transactions to be processed and merges the clients of every shard,
+    /// ordered by client id
+    pub async fn finish(mut self) -> Result<Vec<Client>, RunnerErrors> {
+        let engines = match self.merged.take() {
+            Some(engine) => vec![engine],
+            None => self.stop_workers().await?,
+        };
+        let mut clients = Vec::new();
+        for mut engine in engines {
+            // a sequential run would have settled anything overdue by the last record of the input
+            engine.advance_to(self.now).await?;
+            let shard_clients: Vec<Client> = engine.get_clients().await?.try_collect().await?;
+            clients.extend(shard_clients);
+        }
+        clients.sort_by_key(|client| client.id.0);
+        Ok(clients)
+    }
+
+    /// waits for all queued transactions to be processed, then moves the state of every shard into
+    /// a single engine which processes all further records
+    async fn merge(&mut self) -> Result<(), RunnerErrors> {
+        let mut engines = self.stop_workers().await?.into_iter();
+        // there's always at least one shard
+        let mut merged = engines.next().unwrap();
+        for engine in engines {
+            merged.absorb(engine).await?;
+        }
+        self.merged = Some(merged);
+        self.owners.clear();
+        Ok(())
+    }
+
+    /// lets the workers run to completion, handing back their engines in shard order
+    async fn stop_workers(&mut self) -> Result<Vec<TransactionEngine<T>>, RunnerErrors> {
+        // closing the queues lets the workers run to completion
+        self.queues.clear();
+        let mut engines = Vec::new();
+        for (shard, worker) in self.workers.drain(..).enumerate() {
+            let worker = worker.ok_or(RunnerErrors::WorkerStopped(shard))?;
+            engines.push(
+                worker
+                    .await
+                    .map_err(|_| RunnerErrors::WorkerStopped(shard))??,
+            );
+        }
+        Ok(engines)
+    }
+
+    async fn stopped_worker_error(&mut self, shard: usize) -> RunnerErrors {
+        match self.workers[shard].take() {
+            Some(worker) => match worker.await {
+                Ok(Err(e)) => e.into(),
+                _ => RunnerErrors::WorkerStopped(shard),
+            },
+            None => RunnerErrors::WorkerStopped(shard),
+        }
+    }
+}
+
+async fn run_shard<T: EngineConfig>(
+    mut engine: TransactionEngine<T>,
+    mut queue: mpsc::Receiver<TimedTransaction>,
+    report: OutcomeReporter,
+) -> Result<TransactionEngine<T>, EngineErrors> {
+    while let Some(record) = queue.next().await {
+        let outcome = engine.process_timed_transaction(record.clone()).await?;
+        report(&record.transaction, &outcome);
+    }
+    Ok(engine)
+}
+
+/// picks the shard for a client, stable across runs so the same input always shards the same way
+fn shard_for(client: ClientId, shards: usize) -> usize {
+    client.0 as usize % shards
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,352 @@
+use crate::adapters::memory::InMemoryEngineDeps;
+use crate::domain::engine::tests::test_helpers::{
+    authorize, capture, deposit, dispute, random_transactions, transfer, withdraw,
+};
+use crate::domain::engine::TransactionEngine;
+use crate::domain::model::{
+    AmountInMinorUnits, AmountValidation, AuthorizationExpiry, Client, ClientId, DisputeWindow,
+    Transaction, TransactionId, TransactionOutcome,
+};
+use crate::domain::ports::Engine;
+use crate::input::RejectsReport;
+use crate::runner::{process_async_reader, process_reader, shard_for, ShardedRunner};
+use futures::io::Cursor;
+use futures::TryStreamExt;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+fn ignore_outcome(_: &Transaction, _: &TransactionOutcome) {}
+
+/// the first client routed to a different shard than `client`
+fn other_shard_client(client: ClientId, shards: usize) -> ClientId {
+    (1..)
+        .map(ClientId)
+        .find(|other| shard_for(*other, shards) != shard_for(client, shards))
+        .unwrap()
+}
+
+type EngineFactory = fn() -> TransactionEngine<InMemoryEngineDeps>;
+
+async fn run_sequential(transactions: Vec<Transaction>, new_engine: EngineFactory) -> Vec<Client> {
+    let mut engine = new_engine();
+    for transaction in transactions {
+        engine.process_transaction(transaction).await.unwrap();
+    }
+    let mut clients: Vec<Client> = engine
+        .get_clients()
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    clients.sort_by_key(|client| client.id.0);
+    clients
+}
+
+async fn run_sharded(
+    transactions: Vec<Transaction>,
+    shards: usize,
+    new_engine: EngineFactory,
+) -> Vec<Client> {
+    let engines = (0..shards).map(|_| new_engine()).collect();
+    let mut runner = ShardedRunner::new(engines, ignore_outcome);
+    for transaction in transactions {
+        runner.submit(transaction.into()).await.unwrap();
+    }
+    runner.finish().await.unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn sharded_run_matches_sequential_run() {
+    // test setup
+    let transactions = random_transactions(7, 10_000);
+    let expected = run_sequential(transactions.clone(), TransactionEngine::default).await;
+
+    // test subject
+    let clients = run_sharded(transactions, 8, TransactionEngine::default).await;
+
+    // check results
+    assert_eq!(clients, expected);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn sharded_run_with_dispute_window_matches_sequential_run() {
+    // test setup
+    let transactions = random_transactions(13, 10_000);
+    let new_engine: EngineFactory = || {
+        TransactionEngine::default().with_dispute_window(DisputeWindow::new(Some(200), Some(100)))
+    };
+    let expected = run_sequential(transactions.clone(), new_engine).await;
+
+    // test subject
+    let clients = run_sharded(transactions, 8, new_engine).await;
+
+    // check results
+    assert_eq!(clients, expected);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn sharded_run_with_reused_ids_matches_sequential_run() {
+    // test setup
+    let shards = 8;
+    let other = other_shard_client(ClientId(1), shards);
+    let mut transactions = vec![
+        deposit(ClientId(1), TransactionId(1), 10),
+        // reuses the id of a deposit made on another shard
+        deposit(other, TransactionId(1), 20),
+        withdraw(ClientId(1), TransactionId(2), 5),
+    ];
+    // along with random records reusing the ids of records from other clients
+    for (position, transaction) in random_transactions(17, 2_000).into_iter().enumerate() {
+        transactions.push(transaction);
+        if position % 100 == 99 {
+            let reused = TransactionId(position as u32 / 2);
+            transactions.push(deposit(ClientId(position as u16 % 7 + 1), reused, 5));
+        }
+    }
+    let expected = run_sequential(transactions.clone(), TransactionEngine::default).await;
+
+    // test subject
+    let clients = run_sharded(transactions, shards, TransactionEngine::default).await;
+
+    // check results
+    assert_eq!(clients, expected);
+}
+
+#[tokio::test]
+async fn records_on_other_shards_make_disputes_overdue() {
+    // test setup
+    let shards = 8;
+    let disputing = ClientId(1);
+    let other = other_shard_client(disputing, shards);
+    let transactions = vec![
+        deposit(disputing, TransactionId(1), 10),
+        dispute(disputing, TransactionId(1), None),
+        deposit(other, TransactionId(2), 10),
+        deposit(other, TransactionId(3), 10),
+    ];
+    let new_engine: EngineFactory =
+        || TransactionEngine::default().with_dispute_window(DisputeWindow::new(None, Some(2)));
+    let expected = run_sequential(transactions.clone(), new_engine).await;
+
+    // test subject
+    let clients = run_sharded(transactions, shards, new_engine).await;
+
+    // check results
+    assert_eq!(expected[0].held, AmountInMinorUnits::from(0));
+    assert_eq!(clients, expected);
+}
+
+/// an authorization followed by records of a client on another shard, then `last` if given
+fn authorization_between_shards(shards: usize, last: Option<Transaction>) -> Vec<Transaction> {
+    let other = other_shard_client(ClientId(1), shards);
+    let mut transactions = vec![
+        deposit(ClientId(1), TransactionId(1), 10),
+        authorize(ClientId(1), TransactionId(2), 4),
+        deposit(other, TransactionId(3), 10),
+        deposit(other, TransactionId(4), 10),
+    ];
+    transactions.extend(last);
+    transactions
+}
+
+#[tokio::test]
+async fn records_on_other_shards_expire_authorizations() {
+    // test setup
+    let shards = 8;
+    let capture = capture(ClientId(1), TransactionId(2));
+    let new_engine: EngineFactory = || {
+        TransactionEngine::default().with_authorization_expiry(AuthorizationExpiry::new(Some(2)))
+    };
+
+    for last in [None, Some(capture)] {
+        let transactions = authorization_between_shards(shards, last);
+        let expected = run_sequential(transactions.clone(), new_engine).await;
+
+        // test subject
+        let clients = run_sharded(transactions, shards, new_engine).await;
+
+        // check results
+        assert_eq!(expected[0].held, AmountInMinorUnits::from(0));
+        assert_eq!(expected[0].total, AmountInMinorUnits::from(10));
+        assert_eq!(clients, expected);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn single_shard_matches_sequential_run() {
+    // test setup
+    let transactions = random_transactions(11, 1_000)
+        .into_iter()
+        .filter(|transaction| matches!(transaction, Transaction::Deposit(_)))
+        .collect::<Vec<_>>();
+    let expected = run_sequential(transactions.clone(), TransactionEngine::default).await;
+
+    // test subject
+    let clients = run_sharded(transactions, 1, TransactionEngine::default).await;
+
+    // check results
+    assert_eq!(clients, expected);
+}
+
+#[tokio::test]
+async fn async_reader_produces_same_clients_as_sync_reader() {
+    // test setup
+    let input = "type, client, tx, amount
+deposit, 1, 1, 3.0
+deposit, 2, 2, 2.0
+withdrawal, 1, 3, 1.25
+dispute, 2, 2,
+";
+    let mut sync_engine = TransactionEngine::<InMemoryEngineDeps>::default();
+    process_reader(
+        input.as_bytes(),
+        AmountValidation::Strict,
+        &mut sync_engine,
+        ignore_outcome,
+        &mut RejectsReport::new(io::sink()),
+    )
+    .await
+    .unwrap();
+    let mut async_engine = TransactionEngine::<InMemoryEngineDeps>::default();
+
+    // test subject
+    process_async_reader(
+        Cursor::new(input),
+        AmountValidation::Strict,
+        &mut async_engine,
+        ignore_outcome,
+        &mut RejectsReport::new(io::sink()),
+    )
+    .await
+    .unwrap();
+
+    // check results
+    let mut expected: Vec<Client> = sync_engine
+        .get_clients()
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    let mut clients: Vec<Client> = async_engine
+        .get_clients()
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    expected.sort_by_key(|client| client.id.0);
+    clients.sort_by_key(|client| client.id.0);
+    assert_eq!(expected.len(), 2);
+    assert_eq!(clients, expected);
+}
+
+#[tokio::test]
+async fn malformed_rows_are_reported_and_processing_continues() {
+    // test setup
+    let input = "type, client, tx, amount
+deposit, 1, 1, 3.0
+deposit, x, 2, 2.0
+deposit, 1, 3, 1.0
+";
+    let mut engine = TransactionEngine::<InMemoryEngineDeps>::default();
+    let mut output = Vec::new();
+    let mut rejects = RejectsReport::new(&mut output);
+
+    // test subject
+    process_reader(
+        input.as_bytes(),
+        AmountValidation::Strict,
+        &mut engine,
+        ignore_outcome,
+        &mut rejects,
+    )
+    .await
+    .unwrap();
+
+    // check results
+    assert_eq!(rejects.count(), 1);
+    let clients: Vec<Client> = engine
+        .get_clients()
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(clients[0].available, AmountInMinorUnits::from(4));
+}
+
+// rejections reported by `count_rejections`
+static REJECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+fn count_rejections(_: &Transaction, outcome: &TransactionOutcome) {
+    if let TransactionOutcome::Rejected { .. } = outcome {
+        REJECTIONS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn transfer_between_shards_is_applied() {
+    // test setup
+    let shards = 8;
+    let from = ClientId(1);
+    let to = other_shard_client(from, shards);
+    let input = format!(
+        "type, client, tx, amount, to_client
+deposit, {from}, 1, 10.0,
+transfer, {from}, 2, 4.0, {to}
+deposit, {to}, 3, 1.0,
+withdrawal, {from}, 4, 2.0,
+",
+        from = from.0,
+        to = to.0
+    );
+    let engines = (0..shards)
+        .map(|_| TransactionEngine::<InMemoryEngineDeps>::default())
+        .collect();
+    let mut runner = ShardedRunner::new(engines, count_rejections);
+
+    // test subject
+    runner
+        .submit_reader(
+            input.as_bytes(),
+            AmountValidation::Strict,
+            &mut RejectsReport::new(io::sink()),
+        )
+        .await
+        .unwrap();
+    let clients = runner.finish().await.unwrap();
+
+    // check results
+    assert_eq!(REJECTIONS.load(Ordering::SeqCst), 0);
+    assert_eq!(clients[0].id, from);
+    assert_eq!(clients[0].total, AmountInMinorUnits::from(4));
+    assert_eq!(clients[1].id, to);
+    assert_eq!(clients[1].total, AmountInMinorUnits::from(5));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn sharded_run_with_transfers_matches_sequential_run() {
+    // test setup
+    let mut transactions = Vec::new();
+    for (position, transaction) in random_transactions(19, 2_000).into_iter().enumerate() {
+        transactions.push(transaction);
+        // every so often funds are sent to another client, and some of those transfers disputed
+        if position % 50 == 49 {
+            let from = ClientId(position as u16 % 50 + 1);
+            let tx = TransactionId(10_000 + position as u32);
+            transactions.push(transfer(from, ClientId(position as u16 % 13 + 1), tx, 3));
+            if position % 100 == 99 {
+                transactions.push(dispute(from, tx, None));
+            }
+        }
+    }
+    let expected = run_sequential(transactions.clone(), TransactionEngine::default).await;
+
+    // test subject
+    let clients = run_sharded(transactions, 8, TransactionEngine::default).await;
+
+    // check results
+    assert_eq!(clients, expected);
+}